@@ -1,7 +1,16 @@
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    prost_build::compile_protos(&["proto/messages.proto"], &["proto/"])?;
+    prost_build::Config::new()
+        /* the messages are also exchanged as JSON by the HTTP gateway */
+        .message_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]")
+        .enum_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)] #[serde(rename_all = \"snake_case\")]")
+        /* sorted maps, so the JSON form of a StatsResponse always lists the counters in the same order */
+        .btree_map(["."])
+        .field_attribute("messages.ServerMessage.message", "#[serde(flatten)]")
+        .field_attribute("messages.ErrorResponse.code", "#[serde(with = \"crate::message::error_code\")]")
+        .field_attribute("messages.ClientMessage.deadline_unix_ms", "#[serde(skip_serializing_if = \"crate::message::is_unset\")]")
+        .compile_protos(&["proto/messages.proto"], &["proto/"])?;
 
     Ok(())
 }
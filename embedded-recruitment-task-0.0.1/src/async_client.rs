@@ -0,0 +1,348 @@
+/*
+    Client of the server for tokio, enabled by the `async` feature.
+
+        let client = AsyncClient::connect("127.0.0.1:8080").await?;
+        let (hello, sum) = tokio::join!(client.echo("hello"), client.add(1, 2));
+
+    Requests are pipelined: any number of them can be in flight on the one connection, sent
+    as soon as they are made, without waiting for the previous responses. The server answers
+    in order, so the n-th response read belongs to the n-th request written.
+
+    The connection is owned by two tasks spawned on the current runtime, one writing the
+    requests and one reading the responses. A caller only waits for its response to be handed
+    over, so dropping a request future (a timeout, a `select!` branch that lost) never leaves
+    a frame half written or half read: the request is still sent and its response read and
+    thrown away, the next requests get the right responses.
+*/
+use crate::{
+    client::{connect_error, interleave_families, into_response, unexpected, ClientConfig, ClientError},
+    framing::{self, ClosedBetweenFrames, FrameDecoder},
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, HealthCheck, HealthResponse,
+        ServerMessage, StatsRequest, StatsResponse,
+    },
+};
+use prost::Message;
+use std::{
+    collections::VecDeque,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream, ToSocketAddrs,
+    },
+    sync::{mpsc, oneshot},
+    task::{JoinHandle, JoinSet},
+    time,
+};
+
+/*
+    The async form of client::connect_any: the addresses are tried in the same order
+    (alternating the families), each attempt gets the same head start over the next one and
+    a failure starts the next attempt right away. The first connection wins, the attempts
+    still running are dropped. When every address fails the error lists every attempt.
+*/
+async fn connect_any(addrs: &[SocketAddr], timeout: Option<Duration>, attempt_delay: Duration) -> io::Result<TcpStream> {
+    let mut pending = interleave_families(addrs).into_iter();
+    if pending.len() == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the address does not resolve"));
+    }
+
+    let mut attempts = JoinSet::new();
+    let mut failures = Vec::new();
+    loop {
+        if let Some(addr) = pending.next() {
+            attempts.spawn(async move { (addr, connect_to(addr, timeout).await) });
+        }
+        let waiting_to_start = pending.len() > 0;
+        tokio::select! {
+            attempt = attempts.join_next() => match attempt {
+                Some(Ok((_, Ok(stream)))) => return Ok(stream),
+                /* no reason to wait for the delay, the loop starts the next attempt now */
+                Some(Ok((addr, Err(e)))) => failures.push((addr, e)),
+                Some(Err(e)) => return Err(io::Error::other(e)),
+                None => return Err(connect_error(failures)),
+            },
+            _ = time::sleep(attempt_delay), if waiting_to_start => {}
+        }
+    }
+}
+
+async fn connect_to(addr: SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    match timeout {
+        Some(timeout) => time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connecting timed out"))?,
+        None => TcpStream::connect(addr).await,
+    }
+}
+
+type Responder = oneshot::Sender<Result<ServerMessage, ClientError>>;
+
+/* A request on its way to the writing task */
+struct Request {
+    frame: Vec<u8>,
+    response: Responder,
+}
+
+/* State shared by the two tasks of a connection */
+#[derive(Default)]
+struct Connection {
+    /* who is waiting for the responses, in the order the requests were written */
+    waiting: VecDeque<Responder>,
+    /* why the connection can't be used anymore */
+    closed: Option<(io::ErrorKind, String)>,
+    /* the server closed it cleanly between two responses, see ClientError::Shutdown */
+    shut_down: bool,
+}
+
+impl Connection {
+    /* Marks the connection closed and fails every request still waiting */
+    fn close(&mut self, error: io::Error) {
+        if self.closed.is_none() {
+            self.shut_down = error.get_ref().is_some_and(|inner| inner.is::<ClosedBetweenFrames>());
+            self.closed = Some((error.kind(), error.to_string()));
+        }
+        while let Some(responder) = self.waiting.pop_front() {
+            let _ = responder.send(Err(self.error().into()));
+        }
+    }
+
+    fn error(&self) -> io::Error {
+        match &self.closed {
+            Some(_) if self.shut_down => ClosedBetweenFrames.into(),
+            Some((kind, message)) => io::Error::new(*kind, message.clone()),
+            None => io::Error::new(io::ErrorKind::BrokenPipe, "the connection is closed"),
+        }
+    }
+}
+
+/*
+    A connection to a server usable from any number of tasks at once, cloning it gives
+    another handle on the same connection. The connection is closed once every handle is
+    dropped.
+*/
+#[derive(Clone)]
+pub struct AsyncClient {
+    requests: mpsc::UnboundedSender<Request>,
+    connection: Arc<Mutex<Connection>>,
+    peer: SocketAddr,
+    read_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+}
+
+impl AsyncClient {
+    /* Connects to any of the addresses `addr` resolves to, see connect_any */
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<AsyncClient, ClientError> {
+        AsyncClient::with_config(addr, ClientConfig::default()).await
+    }
+
+    /*
+        Connects applying the timeouts and limits of `config`. `read_timeout` limits how long
+        each request waits for its response, `write_timeout` how long writing one takes, and
+        `request_timeout` is the deadline of every request (see send_within).
+        Must be called from within a tokio runtime, the connection tasks are spawned on it.
+    */
+    pub async fn with_config(addr: impl ToSocketAddrs, config: ClientConfig) -> Result<AsyncClient, ClientError> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host(addr).await?.collect();
+        let stream = connect_any(&addrs, config.connect_timeout, config.attempt_delay).await?;
+        stream.set_nodelay(true)?;
+        let peer = stream.peer_addr()?;
+        let (reader, writer) = stream.into_split();
+
+        let connection = Arc::new(Mutex::new(Connection::default()));
+        let (requests, queue) = mpsc::unbounded_channel();
+        let reading = tokio::spawn(read_responses(reader, config.max_frame_size, Arc::clone(&connection)));
+        tokio::spawn(write_requests(writer, queue, config.write_timeout, Arc::clone(&connection), reading));
+
+        Ok(AsyncClient {
+            requests,
+            connection,
+            peer,
+            read_timeout: config.read_timeout,
+            request_timeout: config.request_timeout,
+        })
+    }
+
+    /* Returns the address of the server */
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /* True once the connection failed, every request made after that fails right away */
+    pub fn is_closed(&self) -> bool {
+        self.connection.lock().unwrap().closed.is_some()
+    }
+
+    /* Sends `content` in an EchoMessage and returns the content echoed back */
+    pub async fn echo(&self, content: &str) -> Result<String, ClientError> {
+        let request = client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        });
+        match self.call(request).await? {
+            server_message::Message::EchoMessage(echo) => Ok(echo.content),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /* Asks the server for `a + b` */
+    pub async fn add(&self, a: i32, b: i32) -> Result<i32, ClientError> {
+        match self.call(client_message::Message::AddRequest(AddRequest { a, b })).await? {
+            server_message::Message::AddResponse(add) => Ok(add.result),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /* Asks the server for its uptime, version and counters */
+    pub async fn stats(&self) -> Result<StatsResponse, ClientError> {
+        match self.call(client_message::Message::StatsRequest(StatsRequest {})).await? {
+            server_message::Message::StatsResponse(stats) => Ok(stats),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /* Asks the server whether it is live and ready to take more clients */
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        match self.call(client_message::Message::HealthCheck(HealthCheck {})).await? {
+            server_message::Message::HealthResponse(health) => Ok(health),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /*
+        Sends any request and returns the response, an ErrorResponse is turned into
+        ClientError::Server.
+    */
+    pub async fn call(&self, request: client_message::Message) -> Result<server_message::Message, ClientError> {
+        into_response(self.send(ClientMessage { message: Some(request), ..Default::default() }).await?)
+    }
+
+    /* Like call, with a deadline of `timeout` for this request (see send_within) */
+    pub async fn call_within(
+        &self,
+        request: client_message::Message,
+        timeout: Duration,
+    ) -> Result<server_message::Message, ClientError> {
+        into_response(self.send_within(ClientMessage { message: Some(request), ..Default::default() }, timeout).await?)
+    }
+
+    /*
+        Sends a ClientMessage as it is and returns the ServerMessage answering it, whatever it
+        is. The request_timeout of the config, if any, is its deadline.
+    */
+    pub async fn send(&self, request: ClientMessage) -> Result<ServerMessage, ClientError> {
+        match self.request_timeout {
+            Some(timeout) => self.send_within(request, timeout).await,
+            None => self.exchange(request, self.read_timeout).await,
+        }
+    }
+
+    /*
+        Sends a request that must be answered within `timeout`. The deadline goes along with the
+        request (`deadline_unix_ms`), a server getting to it too late answers DEADLINE_EXCEEDED
+        instead of serving it. Giving up at the deadline leaves the connection usable.
+    */
+    pub async fn send_within(&self, mut request: ClientMessage, timeout: Duration) -> Result<ServerMessage, ClientError> {
+        request.deadline_unix_ms = (SystemTime::now() + timeout)
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or(0);
+        self.exchange(request, Some(timeout)).await
+    }
+
+    /* Hands the request to the writing task and waits up to `timeout` for its response */
+    async fn exchange(&self, request: ClientMessage, timeout: Option<Duration>) -> Result<ServerMessage, ClientError> {
+        let (responder, response) = oneshot::channel();
+        let request = Request {
+            frame: framing::encode_frame(&request),
+            response: responder,
+        };
+        if self.requests.send(request).is_err() {
+            return Err(self.connection.lock().unwrap().error().into());
+        }
+
+        let response = async {
+            match response.await {
+                Ok(response) => response,
+                Err(_) => Err(self.connection.lock().unwrap().error().into()),
+            }
+        };
+        match timeout {
+            Some(timeout) => time::timeout(timeout, response).await.unwrap_or_else(|_| {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "no response received in time").into())
+            }),
+            None => response.await,
+        }
+    }
+}
+
+/* Writes the requests in the order they were made, until every AsyncClient is dropped */
+async fn write_requests(
+    mut writer: OwnedWriteHalf,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    write_timeout: Option<Duration>,
+    connection: Arc<Mutex<Connection>>,
+    reading: JoinHandle<()>,
+) {
+    while let Some(request) = requests.recv().await {
+        {
+            let mut connection = connection.lock().unwrap();
+            if connection.closed.is_some() {
+                let _ = request.response.send(Err(connection.error().into()));
+                continue;
+            }
+            /* waiting before being written, the response can't arrive before its responder */
+            connection.waiting.push_back(request.response);
+        }
+
+        let written = match write_timeout {
+            Some(timeout) => time::timeout(timeout, writer.write_all(&request.frame))
+                .await
+                .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "sending the request timed out"))),
+            None => writer.write_all(&request.frame).await,
+        };
+        if let Err(e) = written {
+            connection.lock().unwrap().close(e);
+        }
+    }
+
+    /* nobody can make a request or wait for a response anymore */
+    reading.abort();
+    let _ = writer.shutdown().await;
+}
+
+/* Reads the responses and hands each one to the oldest request waiting */
+async fn read_responses(mut reader: OwnedReadHalf, max_frame_size: usize, connection: Arc<Mutex<Connection>>) {
+    let mut decoder = FrameDecoder::new(max_frame_size);
+    let mut buffer = [0u8; 4096];
+    let error = loop {
+        match decoder.next_frame() {
+            Ok(Some(frame)) => {
+                /* a response that does not decode still answers its request, the stream is in sync */
+                let response = ServerMessage::decode(frame.as_slice()).map_err(ClientError::from);
+                match connection.lock().unwrap().waiting.pop_front() {
+                    /* the caller may have given up waiting, the response is dropped then */
+                    Some(responder) => {
+                        let _ = responder.send(response);
+                    }
+                    None => break io::Error::new(io::ErrorKind::InvalidData, "response received without a request"),
+                }
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => break e.into(),
+        }
+        match reader.read(&mut buffer).await {
+            Ok(0) if !decoder.has_partial_frame() => break ClosedBetweenFrames.into(),
+            Ok(0) => break io::Error::new(io::ErrorKind::UnexpectedEof, "the server closed the connection"),
+            Ok(read) => decoder.extend(&buffer[..read]),
+            Err(e) => break e,
+        }
+    };
+    connection.lock().unwrap().close(error);
+}
@@ -0,0 +1,324 @@
+/*
+    Audit log of the requests handled by the server: who asked for what, and how it went.
+
+    The log is a JSON Lines file, one Entry per request:
+
+        {"timestamp_us":1718000000000000,"connection":3,"peer":"127.0.0.1:50312",
+         "message_type":"add_request","outcome":"overflow","latency_us":41,"request_bytes":12}
+
+    `connection` is the id the logs tag the connection with (see logging::connection_span).
+
+    Entries are handed to a writer thread through a bounded queue, the request path never
+    waits for the disk. When the writer can't keep up and the queue is full the entry is
+    dropped and counted (`dropped`), rather than slowing the clients down.
+
+    The file is rotated when it would grow over `max_size` or once it is older than
+    `max_age`: `audit.log` becomes `audit.log.1`, `audit.log.1` becomes `audit.log.2` and so
+    on, the files above `keep` are deleted.
+*/
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/* Where the audit log goes and when it is rotated */
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditConfig {
+    pub path: PathBuf,
+    /* rotate before the file grows over this many bytes */
+    pub max_size: Option<u64>,
+    /* rotate once the file was created this long ago, a restart does not make it younger */
+    pub max_age: Option<Duration>,
+    /* how many rotated files are kept next to the current one */
+    pub keep: usize,
+    /* how many entries may wait for the writer before new ones are dropped */
+    pub queue_size: usize,
+}
+
+impl AuditConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        AuditConfig {
+            path: path.into(),
+            max_size: Some(10 * 1024 * 1024),
+            max_age: None,
+            keep: 5,
+            queue_size: 4096,
+        }
+    }
+}
+
+/* One line of the audit log */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /* microseconds since the UNIX epoch, when the response was ready */
+    pub timestamp_us: u64,
+    pub connection: u64,
+    pub peer: String,
+    /* "echo_message", "add_request", ... or "invalid" when the request did not decode */
+    pub message_type: String,
+    /* "ok" or the error code of the response ("overflow") */
+    pub outcome: String,
+    pub latency_us: u64,
+    /* size of the request payload */
+    pub request_bytes: u64,
+}
+
+enum Command {
+    Write(Entry),
+    /* answered once everything queued before it is on disk */
+    Flush(SyncSender<()>),
+}
+
+/* The audit log of a server, shared by all the connections */
+pub struct AuditLog {
+    queue: Mutex<Option<SyncSender<Command>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    dropped: AtomicU64,
+    overflowing: AtomicBool,
+}
+
+impl AuditLog {
+    /* Opens the file (appending to it) and starts the writer thread */
+    pub fn start(config: AuditConfig) -> io::Result<AuditLog> {
+        let file = RotatingFile::open(config.clone())?;
+        let (queue, commands) = mpsc::sync_channel(config.queue_size.max(1));
+        let writer = thread::Builder::new()
+            .name("audit".to_string())
+            .spawn(move || write_entries(file, commands))?;
+        Ok(AuditLog {
+            queue: Mutex::new(Some(queue)),
+            writer: Mutex::new(Some(writer)),
+            dropped: AtomicU64::new(0),
+            overflowing: AtomicBool::new(false),
+        })
+    }
+
+    /* Starts auditing the requests of the connection `connection` from `peer` */
+    pub fn connection(self: &Arc<Self>, connection: u64, peer: &str) -> AuditSession {
+        AuditSession {
+            log: Arc::clone(self),
+            connection,
+            peer: peer.to_string(),
+        }
+    }
+
+    /* Queues `entry` for the writer, dropping it if the queue is full */
+    pub fn record(&self, entry: Entry) {
+        let result = match &*self.queue.lock().unwrap() {
+            Some(queue) => queue.try_send(Command::Write(entry)),
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                self.overflowing.store(false, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                /* once per overflow, not once per entry */
+                if !self.overflowing.swap(true, Ordering::Relaxed) {
+                    warn!("The audit log can't keep up, entries are being dropped.");
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /* How many entries were lost because the writer could not keep up */
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /* Waits until every entry recorded so far is written to the file */
+    pub fn flush(&self) {
+        let (done, flushed) = mpsc::sync_channel(1);
+        let queued = match &*self.queue.lock().unwrap() {
+            /* a blocking send: the flush must not be dropped like an entry */
+            Some(queue) => queue.send(Command::Flush(done)).is_ok(),
+            None => false,
+        };
+        if queued {
+            let _ = flushed.recv();
+        }
+    }
+
+    /* Writes what is queued and stops the writer, later entries are dropped */
+    pub fn close(&self) {
+        self.queue.lock().unwrap().take();
+        if let Some(writer) = self.writer.lock().unwrap().take() {
+            if writer.join().is_err() {
+                error!("The audit log writer panicked.");
+            }
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/* The audit of one connection */
+pub struct AuditSession {
+    log: Arc<AuditLog>,
+    connection: u64,
+    peer: String,
+}
+
+impl AuditSession {
+    /* Records a request of this connection answered with `outcome` */
+    pub fn request(&self, message_type: &str, outcome: &str, latency: Duration, request_bytes: usize) {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or(0);
+        self.log.record(Entry {
+            timestamp_us,
+            connection: self.connection,
+            peer: self.peer.clone(),
+            message_type: message_type.to_string(),
+            outcome: outcome.to_string(),
+            latency_us: latency.as_micros() as u64,
+            request_bytes: request_bytes as u64,
+        });
+    }
+}
+
+/* Reads every entry of an audit file (the current one or a rotated one), oldest first */
+pub fn read_audit_log(path: impl AsRef<Path>) -> io::Result<Vec<Entry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, e))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/* The loop of the writer thread, until every sender is gone */
+fn write_entries(mut file: RotatingFile, commands: Receiver<Command>) {
+    let mut failed = false;
+    for command in commands {
+        let result = match command {
+            Command::Write(entry) => file.write(&entry),
+            Command::Flush(done) => {
+                let result = file.flush();
+                let _ = done.send(());
+                result
+            }
+        };
+        /* an audit log that can't be written must not take the server down, say it once */
+        match result {
+            Err(e) if !failed => {
+                error!("Failed to write the audit log {}, entries are being lost: {}", file.path().display(), e);
+                failed = true;
+            }
+            Err(_) => {}
+            Ok(()) => failed = false,
+        }
+    }
+    if let Err(e) = file.flush() {
+        error!("Failed to write the audit log {}: {}", file.path().display(), e);
+    }
+}
+
+/* The current audit file, rotated according to the config */
+struct RotatingFile {
+    config: AuditConfig,
+    file: BufWriter<File>,
+    size: u64,
+    /* when the file was created, its last change on the filesystems that don't keep that */
+    created: SystemTime,
+}
+
+impl RotatingFile {
+    fn open(config: AuditConfig) -> io::Result<RotatingFile> {
+        let file = OpenOptions::new().create(true).append(true).open(&config.path)?;
+        let metadata = file.metadata()?;
+        let created = metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        Ok(RotatingFile {
+            config,
+            file: BufWriter::new(file),
+            size: metadata.len(),
+            created,
+        })
+    }
+
+    fn path(&self) -> &Path {
+        &self.config.path
+    }
+
+    fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry).expect("entries are serializable");
+        line.push('\n');
+
+        let too_big = self
+            .config
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + line.len() as u64 > max_size);
+        /* a clock set back makes the file younger, not older */
+        let age = self.created.elapsed().unwrap_or_default();
+        let too_old = self.config.max_age.is_some_and(|max_age| age >= max_age);
+        if too_big || too_old {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    /* Shifts the rotated files by one, drops the oldest and starts a new current file */
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let rotated = |index: usize| {
+            let mut name = self.config.path.clone().into_os_string();
+            name.push(format!(".{}", index));
+            PathBuf::from(name)
+        };
+
+        if self.config.keep == 0 {
+            fs::remove_file(&self.config.path)?;
+        } else {
+            match fs::remove_file(rotated(self.config.keep)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            for index in (1..self.config.keep).rev() {
+                match fs::rename(rotated(index), rotated(index + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.config.path, rotated(1))?;
+        }
+
+        *self = RotatingFile::open(self.config.clone())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+/*
+    A command line client to poke a running server, see `client --help` for the options.
+
+        client --addr 127.0.0.1:8080 echo hello world
+        client --addr 127.0.0.1:8080 add 1 2
+        client --addr 127.0.0.1:8080 repl --history ~/.client_history
+
+    Every response is printed as the JSON form of the decoded ServerMessage, the same one the
+    HTTP gateway answers with. Without a command the client starts the REPL.
+
+    Exit codes:
+        0   the server answered the request
+        1   the request could not be sent or its response could not be read
+        2   the server answered with an ErrorResponse
+*/
+use clap::{Parser, Subcommand};
+use embedded_recruitment_task::{
+    client::{Client, ClientConfig, ClientError},
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, HealthCheck, ServerMessage, StatsRequest,
+    },
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process,
+    time::Duration,
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_ERROR_RESPONSE: i32 = 2;
+
+/// Sends Echo, Add, Stats and HealthCheck requests to a server and prints the responses.
+#[derive(Debug, Parser)]
+#[command(name = "client", version)]
+struct Options {
+    /// Address of the server
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// How long connecting and waiting for a response may take, sent as the deadline of the request (milliseconds)
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    timeout_ms: u64,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Sends an EchoMessage with the words given
+    Echo {
+        #[arg(required = true)]
+        text: Vec<String>,
+    },
+    /// Sends an AddRequest
+    Add {
+        #[arg(allow_negative_numbers = true)]
+        a: i32,
+        #[arg(allow_negative_numbers = true)]
+        b: i32,
+    },
+    /// Sends a StatsRequest (uptime, version and counters of the server)
+    Stats,
+    /// Sends a HealthCheck (whether the server is live and ready to take more clients)
+    Health,
+    /// Reads commands from the terminal (line editing, Up/Down history recall), keeping the connection open between them
+    Repl {
+        /// File the history is loaded from and appended to
+        #[arg(long, value_name = "PATH")]
+        history: Option<PathBuf>,
+    },
+}
+
+fn main() {
+    let options = Options::parse();
+    let mut connection = Connection::new(&options.addr, Duration::from_millis(options.timeout_ms));
+
+    let request = match options.command {
+        Some(Command::Echo { text }) => echo_request(&text.join(" ")),
+        Some(Command::Add { a, b }) => add_request(a, b),
+        Some(Command::Stats) => stats_request(),
+        Some(Command::Health) => health_check(),
+        Some(Command::Repl { history }) => process::exit(repl(&mut connection, history)),
+        None => process::exit(repl(&mut connection, None)),
+    };
+
+    process::exit(match connection.call(request) {
+        Ok(response) => {
+            println!("{}", to_json(&response));
+            if is_error(&response) {
+                EXIT_ERROR_RESPONSE
+            } else {
+                EXIT_OK
+            }
+        }
+        Err(e) => {
+            eprintln!("request to {} failed: {}", options.addr, e);
+            EXIT_FAILURE
+        }
+    });
+}
+
+/* A connection opened on the first request and opened again after a failure */
+struct Connection {
+    addr: String,
+    config: ClientConfig,
+    client: Option<Client>,
+}
+
+impl Connection {
+    fn new(addr: &str, timeout: Duration) -> Self {
+        Connection {
+            addr: addr.to_string(),
+            config: ClientConfig {
+                connect_timeout: Some(timeout),
+                read_timeout: Some(timeout),
+                write_timeout: Some(timeout),
+                request_timeout: Some(timeout),
+                ..ClientConfig::default()
+            },
+            client: None,
+        }
+    }
+
+    /* Sends one request and waits for its response, whatever it is */
+    fn call(&mut self, request: ClientMessage) -> Result<ServerMessage, ClientError> {
+        let client = match &mut self.client {
+            Some(client) => client,
+            None => self.client.insert(Client::with_config(self.addr.as_str(), self.config.clone())?),
+        };
+        let result = client.send(request);
+        if result.is_err() {
+            /* the stream may be in the middle of a frame, start over with a new one next time */
+            self.client = None;
+        }
+        result
+    }
+}
+
+const REPL_HELP: &str = "\
+commands:
+    echo <text>     send an EchoMessage
+    add <a> <b>     send an AddRequest
+    stats           send a StatsRequest
+    health          send a HealthCheck
+    history         list the previous commands
+    !!              run the previous command again
+    !<n>            run the command number <n> of the history again
+    help            show this help
+    quit            leave (Ctrl-D works too)
+
+The line can be edited in place (arrows, Home/End, Ctrl-W, ...), Up and Down go through the
+history and Ctrl-R searches it. Ctrl-C drops the line being typed.";
+
+/*
+    Runs the commands typed on stdin until `quit` or the end of the input. On a terminal the
+    line is read with rustyline (line editing, Up/Down recall, Ctrl-R search), piped input is
+    read line by line. The commands that were run are kept in the history, with their `!`
+    references already expanded. The history file holds one command per line.
+    Returns the exit code of the last request.
+*/
+fn repl(connection: &mut Connection, history_path: Option<PathBuf>) -> i32 {
+    let mut history = load_history(history_path.as_ref());
+    let mut history_file = history_path.and_then(|path| match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("can't write the history to {}: {}", path.display(), e);
+            None
+        }
+    });
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("failed to set up the terminal: {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+    for entry in &history {
+        let _ = editor.add_history_entry(entry.as_str());
+    }
+    let mut exit_code = EXIT_OK;
+
+    /* the connection is only opened by the first command, the server may not be up yet */
+    println!("using {}, type `help` for the commands", connection.addr);
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return exit_code,
+            Err(e) => {
+                eprintln!("failed to read the input: {}", e);
+                return EXIT_FAILURE;
+            }
+        };
+
+        let line = match expand_history(line.trim(), &history) {
+            Ok(line) => line,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        let (command, arguments) = line.split_once(' ').unwrap_or((&line, ""));
+        let request = match command {
+            "" => continue,
+            "quit" | "exit" => return exit_code,
+            "help" => {
+                println!("{}", REPL_HELP);
+                continue;
+            }
+            "history" => {
+                for (index, entry) in history.iter().enumerate() {
+                    println!("{:>4}  {}", index + 1, entry);
+                }
+                continue;
+            }
+            "echo" => echo_request(arguments),
+            "stats" => stats_request(),
+            "health" => health_check(),
+            "add" => match parse_add(arguments) {
+                Ok((a, b)) => add_request(a, b),
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            },
+            _ => {
+                println!("unknown command `{}`, type `help` for the commands", command);
+                continue;
+            }
+        };
+
+        if let Some(file) = &mut history_file {
+            let _ = writeln!(file, "{}", line);
+        }
+        let _ = editor.add_history_entry(line.as_str());
+        history.push(line);
+
+        exit_code = match connection.call(request) {
+            Ok(response) => {
+                println!("{}", to_json(&response));
+                if is_error(&response) {
+                    EXIT_ERROR_RESPONSE
+                } else {
+                    EXIT_OK
+                }
+            }
+            Err(e) => {
+                println!("request failed: {}", e);
+                EXIT_FAILURE
+            }
+        };
+    }
+}
+
+fn load_history(path: Option<&PathBuf>) -> Vec<String> {
+    path.and_then(|path| fs::read_to_string(path).ok())
+        .map(|text| text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/* Replaces `!!` and `!<n>` with the command they refer to */
+fn expand_history(line: &str, history: &[String]) -> Result<String, String> {
+    let reference = match line.strip_prefix('!') {
+        Some(reference) => reference,
+        None => return Ok(line.to_string()),
+    };
+    let entry = if reference == "!" {
+        history.last()
+    } else {
+        reference
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .and_then(|index| history.get(index))
+    };
+    match entry {
+        Some(entry) => {
+            println!("{}", entry);
+            Ok(entry.clone())
+        }
+        None => Err(format!("{}: no such command in the history", line)),
+    }
+}
+
+fn parse_add(arguments: &str) -> Result<(i32, i32), String> {
+    let numbers: Vec<&str> = arguments.split_whitespace().collect();
+    match numbers.as_slice() {
+        [a, b] => match (a.parse(), b.parse()) {
+            (Ok(a), Ok(b)) => Ok((a, b)),
+            _ => Err(format!("`{}` and `{}` must both be 32 bit integers", a, b)),
+        },
+        _ => Err("usage: add <a> <b>".to_string()),
+    }
+}
+
+fn echo_request(content: &str) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+fn add_request(a: i32, b: i32) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a, b })),
+        ..Default::default()
+    }
+}
+
+fn stats_request() -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::StatsRequest(StatsRequest {})),
+        ..Default::default()
+    }
+}
+
+fn health_check() -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::HealthCheck(HealthCheck {})),
+        ..Default::default()
+    }
+}
+
+fn is_error(response: &ServerMessage) -> bool {
+    matches!(response.message, Some(server_message::Message::ErrorResponse(_)))
+}
+
+fn to_json(response: &ServerMessage) -> String {
+    serde_json::to_string(response).unwrap_or_else(|e| format!("{:?} ({})", response, e))
+}
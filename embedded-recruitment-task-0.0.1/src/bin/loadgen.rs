@@ -0,0 +1,333 @@
+/*
+    Load generator for the server, see `loadgen --help` for the options.
+
+        loadgen --addr 127.0.0.1:8080 --connections 8 --duration-ms 10000
+        loadgen --addr 127.0.0.1:8080 --rate 2000 --echo-percent 80 --json
+
+    Every connection sends a request and waits for its response before sending the next one.
+    Without --rate they go as fast as the server answers, with --rate the requests are spread
+    evenly over the connections and the latency is measured from the time a request was due,
+    so a server falling behind shows up in the percentiles instead of slowing the load down.
+
+    The mix of Echo and Add requests is deterministic (no randomness) so two runs send exactly
+    the same traffic. A response that does not match its request counts as an error.
+*/
+use clap::Parser;
+use embedded_recruitment_task::{
+    client::{Client, ClientConfig, ClientError},
+    message::{client_message, server_message, AddRequest, AddResponse, ClientMessage, EchoMessage},
+};
+use serde::Serialize;
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    process,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Drives Echo and Add requests against a server and reports throughput and latency.
+#[derive(Debug, Parser)]
+#[command(name = "loadgen", version)]
+struct Options {
+    /// Address of the server
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// Number of concurrent connections
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    connections: u32,
+
+    /// Requests per second over all the connections, 0 for as fast as possible
+    #[arg(long, default_value_t = 0)]
+    rate: u64,
+
+    /// How long to run (milliseconds)
+    #[arg(long, value_name = "MS", default_value_t = 10_000)]
+    duration_ms: u64,
+
+    /// Stop after this many requests over all the connections, even before the duration
+    #[arg(long)]
+    requests: Option<u64>,
+
+    /// Share of Echo requests in the mix, the rest are Add requests
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u8).range(0..=100))]
+    echo_percent: u8,
+
+    /// Size of the content of the Echo requests (bytes)
+    #[arg(long, value_name = "BYTES", default_value_t = 32)]
+    payload_size: usize,
+
+    /// How long connecting and waiting for a response may take (milliseconds)
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    timeout_ms: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/* What one connection measured */
+#[derive(Debug, Default)]
+struct ConnectionResult {
+    echo_requests: u64,
+    add_requests: u64,
+    latencies_us: Vec<u64>,
+    io_errors: u64,
+    error_responses: u64,
+    mismatches: u64,
+}
+
+/* The report printed at the end, all latencies are in microseconds */
+#[derive(Debug, Serialize)]
+struct Report {
+    connections: u32,
+    target_rate: u64,
+    duration_ms: u64,
+    requests: u64,
+    echo_requests: u64,
+    add_requests: u64,
+    errors: Errors,
+    throughput: f64,
+    latency_us: Latency,
+}
+
+#[derive(Debug, Serialize)]
+struct Errors {
+    /* the request could not be sent or its response not read */
+    io: u64,
+    /* the server answered with an ErrorResponse */
+    error_responses: u64,
+    /* the response does not match the request */
+    mismatches: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Latency {
+    min: u64,
+    mean: u64,
+    p50: u64,
+    p99: u64,
+    p999: u64,
+    max: u64,
+}
+
+fn main() {
+    let options = Options::parse();
+    /* every address it resolves to, connect_any tries them the happy-eyeballs way */
+    let addrs: Arc<[SocketAddr]> = match options.addr.to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(e) => {
+            eprintln!("{}: {}", options.addr, e);
+            process::exit(1);
+        }
+    };
+    if addrs.is_empty() {
+        eprintln!("{} does not resolve", options.addr);
+        process::exit(1);
+    }
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..options.connections)
+        .map(|index| {
+            let load = Load::new(&options, index);
+            let addrs = Arc::clone(&addrs);
+            thread::spawn(move || load.run(&addrs, started))
+        })
+        .collect();
+    let results: Vec<ConnectionResult> = workers
+        .into_iter()
+        .map(|worker| worker.join().expect("a connection thread panicked"))
+        .collect();
+
+    let report = build_report(&options, results, started.elapsed());
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report).expect("the report is serializable"));
+    } else {
+        print_report(&report);
+    }
+}
+
+/*
+    The share of the load driven by one connection. The requests form one sequence over all
+    the connections: connection `first` sends the requests first, first + stride, ...
+*/
+struct Load {
+    first: u64,
+    stride: u64,
+    timeout: Duration,
+    deadline: Duration,
+    requests: Option<u64>,
+    interval: Option<Duration>,
+    echo_percent: u64,
+    payload: String,
+}
+
+impl Load {
+    fn new(options: &Options, index: u32) -> Self {
+        let connections = u64::from(options.connections);
+        /* the first connections take one more request when the total does not split evenly */
+        let requests = options
+            .requests
+            .map(|total| total / connections + u64::from(u64::from(index) < total % connections));
+        let interval = (options.rate > 0).then(|| Duration::from_secs_f64(1.0 / options.rate as f64));
+        Load {
+            first: u64::from(index),
+            stride: connections,
+            timeout: Duration::from_millis(options.timeout_ms),
+            deadline: Duration::from_millis(options.duration_ms),
+            requests,
+            interval,
+            echo_percent: u64::from(options.echo_percent),
+            payload: "x".repeat(options.payload_size),
+        }
+    }
+
+    fn run(&self, addrs: &[SocketAddr], started: Instant) -> ConnectionResult {
+        let mut result = ConnectionResult::default();
+        let mut client: Option<Client> = None;
+        let mut sent: u64 = 0;
+
+        while started.elapsed() < self.deadline && self.requests.is_none_or(|requests| sent < requests) {
+            let index = self.first + sent * self.stride;
+            /* with a target rate wait for the time this request is due */
+            let due = match self.interval {
+                Some(interval) => {
+                    let due = started + interval.mul_f64(index as f64);
+                    if due >= started + self.deadline {
+                        break;
+                    }
+                    if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                        thread::sleep(wait);
+                    }
+                    due
+                }
+                None => Instant::now(),
+            };
+
+            let (request, expected) = self.request(index);
+            sent += 1;
+            match request.message {
+                Some(client_message::Message::EchoMessage(_)) => result.echo_requests += 1,
+                _ => result.add_requests += 1,
+            }
+            let response = match &mut client {
+                Some(client) => client.send(request),
+                None => self.connect(addrs).and_then(|new_client| client.insert(new_client).send(request)),
+            };
+            match response {
+                Ok(response) => {
+                    result.latencies_us.push(due.elapsed().as_micros() as u64);
+                    match response.message {
+                        Some(server_message::Message::ErrorResponse(_)) => result.error_responses += 1,
+                        message if message != Some(expected) => result.mismatches += 1,
+                        _ => {}
+                    }
+                }
+                Err(_) => {
+                    result.io_errors += 1;
+                    /* start over with a new connection, the old one may be in the middle of a frame */
+                    client = None;
+                }
+            }
+        }
+        result
+    }
+
+    /* The request number `index` of the mix, with the response it must get */
+    fn request(&self, index: u64) -> (ClientMessage, server_message::Message) {
+        /* spreads the Echo requests evenly: request `index` is an Echo when it crosses a multiple of 100 */
+        let is_echo = (index + 1) * self.echo_percent / 100 > index * self.echo_percent / 100;
+        if is_echo {
+            let echo = EchoMessage {
+                content: self.payload.clone(),
+            };
+            let request = client_message::Message::EchoMessage(echo.clone());
+            (ClientMessage { message: Some(request), ..Default::default() }, server_message::Message::EchoMessage(echo))
+        } else {
+            let (a, b) = ((index % 1000) as i32, 7);
+            let request = client_message::Message::AddRequest(AddRequest { a, b });
+            let response = server_message::Message::AddResponse(AddResponse { result: a + b });
+            (ClientMessage { message: Some(request), ..Default::default() }, response)
+        }
+    }
+
+    fn connect(&self, addrs: &[SocketAddr]) -> Result<Client, ClientError> {
+        let config = ClientConfig {
+            connect_timeout: Some(self.timeout),
+            read_timeout: Some(self.timeout),
+            write_timeout: Some(self.timeout),
+            ..ClientConfig::default()
+        };
+        Client::with_config(addrs, config)
+    }
+}
+
+fn build_report(options: &Options, results: Vec<ConnectionResult>, elapsed: Duration) -> Report {
+    let (mut echo_requests, mut add_requests) = (0, 0);
+    let mut latencies: Vec<u64> = Vec::new();
+    let mut errors = Errors {
+        io: 0,
+        error_responses: 0,
+        mismatches: 0,
+    };
+    for result in results {
+        echo_requests += result.echo_requests;
+        add_requests += result.add_requests;
+        latencies.extend(result.latencies_us);
+        errors.io += result.io_errors;
+        errors.error_responses += result.error_responses;
+        errors.mismatches += result.mismatches;
+    }
+    latencies.sort_unstable();
+
+    Report {
+        connections: options.connections,
+        target_rate: options.rate,
+        duration_ms: elapsed.as_millis() as u64,
+        requests: echo_requests + add_requests,
+        echo_requests,
+        add_requests,
+        errors,
+        throughput: latencies.len() as f64 / elapsed.as_secs_f64(),
+        latency_us: Latency {
+            min: latencies.first().copied().unwrap_or(0),
+            mean: latencies.iter().sum::<u64>().checked_div(latencies.len() as u64).unwrap_or(0),
+            p50: percentile(&latencies, 50.0),
+            p99: percentile(&latencies, 99.0),
+            p999: percentile(&latencies, 99.9),
+            max: latencies.last().copied().unwrap_or(0),
+        },
+    }
+}
+
+/* Nearest-rank percentile of sorted values, 0 when there are none */
+fn percentile(sorted: &[u64], percent: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn print_report(report: &Report) {
+    let rate = match report.target_rate {
+        0 => "as fast as possible".to_string(),
+        rate => format!("{} req/s", rate),
+    };
+    println!("connections: {} ({})", report.connections, rate);
+    println!(
+        "requests:    {} in {} ms ({} echo, {} add)",
+        report.requests, report.duration_ms, report.echo_requests, report.add_requests
+    );
+    println!("throughput:  {:.1} req/s", report.throughput);
+    println!(
+        "errors:      {} io, {} error responses, {} mismatches",
+        report.errors.io, report.errors.error_responses, report.errors.mismatches
+    );
+    let latency = &report.latency_us;
+    println!(
+        "latency:     min {} us, mean {} us, p50 {} us, p99 {} us, p999 {} us, max {} us",
+        latency.min, latency.mean, latency.p50, latency.p99, latency.p999, latency.max
+    );
+}
@@ -0,0 +1,235 @@
+/*
+    Replays a traffic recording against a server and diffs the responses, see `replay --help`.
+
+        server --record traffic.jsonl
+        replay --addr 127.0.0.1:9000 traffic.jsonl
+
+    Every recorded session is replayed on its own connection, one after the other, sending the
+    recorded request frames byte for byte. Only a request deadline changes: it is a wall-clock
+    time, so it is moved by as long as the request is replayed after it was recorded. Each
+    response is decoded and compared with the one recorded for the same request (leaving out
+    the uptime and the connection counts of a StatsResponse or HealthResponse), the differences
+    are printed with both responses.
+
+    Exit codes:
+        0   every response matched the recording
+        1   the recording could not be read or the server not reached
+        2   some responses differ from the recording
+*/
+use clap::Parser;
+use embedded_recruitment_task::{
+    client, framing,
+    message::{server_message, ClientMessage, ServerMessage},
+    recording::{self, Direction, Record},
+};
+use prost::Message;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Write},
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    process, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_DIFFERENT: i32 = 2;
+
+/* Largest response read back, well above anything the server sends */
+const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Re-sends a recorded session against a server and diffs the responses.
+#[derive(Debug, Parser)]
+#[command(name = "replay", version)]
+struct Options {
+    /// Recording written by `server --record`
+    recording: PathBuf,
+
+    /// Address of the server to replay against
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// Only replay these sessions (repeat the option for several)
+    #[arg(long)]
+    session: Vec<u64>,
+
+    /// Wait between two requests as long as the client did when it was recorded
+    #[arg(long)]
+    keep_timing: bool,
+
+    /// How long connecting and waiting for a response may take (milliseconds)
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    timeout_ms: u64,
+}
+
+/* A recorded request with the response the server gave to it */
+struct Exchange {
+    request: Record,
+    response: Option<Record>,
+}
+
+fn main() {
+    let options = Options::parse();
+    let records = match recording::read_recording(&options.recording) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", options.recording.display(), e);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+
+    let sessions = sessions(records, &options.session);
+    let (mut requests, mut differences) = (0, 0);
+    for (session, exchanges) in &sessions {
+        match replay_session(&options, *session, exchanges) {
+            Ok(different) => {
+                requests += exchanges.len();
+                differences += different;
+            }
+            Err(e) => {
+                eprintln!("session {}: {}", session, e);
+                process::exit(EXIT_FAILURE);
+            }
+        }
+    }
+
+    println!(
+        "replayed {} requests of {} sessions, {} differences",
+        requests,
+        sessions.len(),
+        differences
+    );
+    process::exit(if differences == 0 { EXIT_OK } else { EXIT_DIFFERENT });
+}
+
+/* Groups the records by session and pairs every request with the response that followed it */
+fn sessions(records: Vec<Record>, only: &[u64]) -> BTreeMap<u64, Vec<Exchange>> {
+    let mut sessions: BTreeMap<u64, Vec<Exchange>> = BTreeMap::new();
+    let mut unanswered: BTreeMap<u64, VecDeque<usize>> = BTreeMap::new();
+
+    for record in records {
+        if !only.is_empty() && !only.contains(&record.session) {
+            continue;
+        }
+        let exchanges = sessions.entry(record.session).or_default();
+        match record.direction {
+            Direction::Request => {
+                unanswered.entry(record.session).or_default().push_back(exchanges.len());
+                exchanges.push(Exchange {
+                    request: record,
+                    response: None,
+                });
+            }
+            Direction::Response => {
+                /* the responses come in the order of the requests */
+                if let Some(index) = unanswered.get_mut(&record.session).and_then(VecDeque::pop_front) {
+                    exchanges[index].response = Some(record);
+                }
+            }
+        }
+    }
+    sessions.retain(|_, exchanges| !exchanges.is_empty());
+    sessions
+}
+
+/* Replays one session on a new connection, returns the number of responses that differ */
+fn replay_session(options: &Options, session: u64, exchanges: &[Exchange]) -> io::Result<usize> {
+    let timeout = Duration::from_millis(options.timeout_ms);
+    let addrs: Vec<SocketAddr> = options.addr.to_socket_addrs()?.collect();
+    let mut stream = client::connect_any(&addrs, Some(timeout), client::DEFAULT_ATTEMPT_DELAY)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut differences = 0;
+    let mut previous_timestamp = None;
+    for (number, exchange) in exchanges.iter().enumerate() {
+        if let (true, Some(previous)) = (options.keep_timing, previous_timestamp) {
+            thread::sleep(Duration::from_micros(exchange.request.timestamp_us.saturating_sub(previous)));
+        }
+        previous_timestamp = Some(exchange.request.timestamp_us);
+
+        let payload = exchange
+            .request
+            .frame_bytes()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("request {} is not valid hex", number + 1)))?;
+        let payload = rebase_deadline(payload, exchange.request.timestamp_us);
+        let mut frame = Vec::with_capacity(payload.len() + 10);
+        prost::encoding::encode_varint(payload.len() as u64, &mut frame);
+        frame.extend_from_slice(&payload);
+
+        let replayed = stream
+            .write_all(&frame)
+            .and_then(|_| framing::read_frame(&mut stream, MAX_RESPONSE_SIZE));
+        let recorded = exchange.response.as_ref().and_then(Record::frame_bytes);
+
+        let failed = replayed.is_err();
+        let same = match (&replayed, &recorded) {
+            (Ok(replayed), Some(recorded)) => same_response(replayed, recorded),
+            _ => false,
+        };
+        if !same {
+            differences += 1;
+            println!("session {} ({}), request {}: {}", session, exchange.request.peer, number + 1, exchange.request.message);
+            println!("  recorded: {}", describe(recorded.ok_or("no response recorded".to_string())));
+            println!("  replayed: {}", describe(replayed.map_err(|e| e.to_string())));
+            if failed {
+                /* the connection is gone or out of sync, the rest of the session can't be compared */
+                break;
+            }
+        }
+    }
+    Ok(differences)
+}
+
+/*
+    Moves the deadline of a recorded request by as long as it is replayed after `recorded_us`, the
+    server gets the time it had back then. A deadline that had already passed stays passed.
+*/
+fn rebase_deadline(payload: Vec<u8>, recorded_us: u64) -> Vec<u8> {
+    let mut request = match ClientMessage::decode(payload.as_slice()) {
+        Ok(request) if request.deadline_unix_ms != 0 => request,
+        _ => return payload,
+    };
+    let now_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as u64);
+    let budget_ms = request.deadline_unix_ms as i64 - (recorded_us / 1000) as i64;
+    /* 0 means no deadline, a passed one must not turn into that */
+    request.deadline_unix_ms = now_unix_ms.saturating_add_signed(budget_ms).max(1);
+    request.encode_to_vec()
+}
+
+/*
+    Compares the responses decoded, two encodings of the same message are the same response.
+    The fields that change from one run of the server to the next are left out.
+*/
+fn same_response(replayed: &[u8], recorded: &[u8]) -> bool {
+    match (ServerMessage::decode(replayed), ServerMessage::decode(recorded)) {
+        (Ok(replayed), Ok(recorded)) => without_volatile_fields(replayed) == without_volatile_fields(recorded),
+        _ => replayed == recorded,
+    }
+}
+
+/* Clears the uptime and the connection counts, they depend on when and next to what a request is replayed */
+fn without_volatile_fields(mut response: ServerMessage) -> ServerMessage {
+    match &mut response.message {
+        Some(server_message::Message::StatsResponse(stats)) => {
+            stats.uptime_ms = 0;
+            stats.active_connections = 0;
+        }
+        Some(server_message::Message::HealthResponse(health)) => health.active_connections = 0,
+        _ => {}
+    }
+    response
+}
+
+fn describe(response: Result<Vec<u8>, String>) -> String {
+    match response {
+        Ok(frame) => match ServerMessage::decode(frame.as_slice()) {
+            Ok(message) => serde_json::to_string(&message).expect("messages are serializable"),
+            Err(e) => format!("undecodable response ({})", e),
+        },
+        Err(e) => e,
+    }
+}
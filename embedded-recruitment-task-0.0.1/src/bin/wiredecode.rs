@@ -0,0 +1,243 @@
+/*
+    Decodes a capture of the bytes sent one way over a connection, see `wiredecode --help`.
+
+        wiredecode capture.bin
+        tcpdump ... | xxd | wiredecode --direction server
+
+    The capture is either the raw bytes or a hex dump of them. Plain hex (with or without
+    spaces and 0x prefixes) and the output of `xxd` and `hexdump -C` are understood, their
+    offset and ASCII columns are skipped. With --format auto (the default) an input made only
+    of hex dump lines is read as hex, anything else as raw bytes.
+
+    The bytes are split into frames and each frame is decoded as a ClientMessage (the bytes
+    sent by a client) or a ServerMessage (--direction server). Every frame is printed with its
+    offset in the capture and the message as JSON.
+
+    Exit codes:
+        0   every frame was decoded
+        1   the capture could not be read
+        2   some bytes could not be decoded (invalid frame or message, truncated capture)
+*/
+use clap::{Parser, ValueEnum};
+use embedded_recruitment_task::{
+    framing, hex,
+    message::{ClientMessage, ServerMessage},
+};
+use prost::Message;
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+    process,
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_UNDECODABLE: i32 = 2;
+
+/// Splits a captured byte stream into frames and decodes them.
+#[derive(Debug, Parser)]
+#[command(name = "wiredecode", version)]
+struct Options {
+    /// Capture file, stdin when missing
+    file: Option<PathBuf>,
+
+    /// Who sent the captured bytes
+    #[arg(long, value_enum, default_value_t = Direction::Client)]
+    direction: Direction,
+
+    /// How the capture is written
+    #[arg(long, value_enum, default_value_t = Format::Auto)]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Direction {
+    /// Bytes sent by a client, decoded as ClientMessage
+    Client,
+    /// Bytes sent by a server, decoded as ServerMessage
+    Server,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// Hex if the capture parses as a hex dump, raw bytes otherwise
+    Auto,
+    /// A hex dump
+    Hex,
+    /// The raw bytes
+    Binary,
+}
+
+fn main() {
+    let options = Options::parse();
+    let input = match read_input(options.file.as_ref()) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("failed to read the capture: {}", e);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+
+    let bytes = match options.format {
+        Format::Binary => input,
+        Format::Hex => match parse_hex(&input) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("the capture is not a hex dump: {}", e);
+                process::exit(EXIT_FAILURE);
+            }
+        },
+        Format::Auto => parse_hex(&input).unwrap_or(input),
+    };
+
+    process::exit(if decode_stream(&bytes, options.direction) {
+        EXIT_OK
+    } else {
+        EXIT_UNDECODABLE
+    });
+}
+
+fn read_input(file: Option<&PathBuf>) -> io::Result<Vec<u8>> {
+    match file {
+        Some(path) => fs::read(path),
+        None => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+/*
+    Parses a hex dump. Only the lines shaped like the output of xxd (`00000010: ...`) or
+    hexdump -C (`00000010  ... |ASCII|`) start with an offset column, it is ignored with their
+    ASCII column. Any other line is hex bytes only, grouped or not (`060a040a 02686906`).
+*/
+fn parse_hex(input: &[u8]) -> Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(input).map_err(|_| "not text".to_string())?;
+    let hexdump = text.lines().any(|line| line.contains('|'));
+    let mut bytes = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let mut line = line.trim();
+        /* hexdump -C puts the ASCII column between bars, after the offset and the bytes */
+        let ascii_column = line.find('|');
+        if let Some(bar) = ascii_column {
+            line = &line[..bar];
+        }
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        if let Some(offset) = tokens.first().and_then(|token| token.strip_suffix(':')) {
+            if !is_hex(offset) {
+                return Err(format!("line {}: invalid offset `{}`", number + 1, offset));
+            }
+            /* xxd separates the ASCII column with two spaces, the hex groups with one */
+            let hex_part = line.split_once(':').map(|(_, rest)| rest.trim_start()).unwrap_or("");
+            tokens = hex_part.split("  ").next().unwrap_or("").split_whitespace().collect();
+        } else if ascii_column.is_some() {
+            match tokens.first() {
+                Some(offset) if is_hex(offset) => {
+                    tokens.remove(0);
+                }
+                _ => return Err(format!("line {}: missing offset before the bytes", number + 1)),
+            }
+        } else if hexdump && tokens.len() == 1 {
+            /* hexdump -C ends with the offset after the last byte alone on its line */
+            continue;
+        }
+
+        for token in tokens {
+            let digits = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token)
+                .trim_end_matches(',');
+            match hex::decode(digits) {
+                Some(decoded) if !decoded.is_empty() => bytes.extend(decoded),
+                _ => return Err(format!("line {}: `{}` is not hex bytes", number + 1, token)),
+            }
+        }
+    }
+
+    if bytes.is_empty() && !text.trim().is_empty() {
+        return Err("no hex bytes found".to_string());
+    }
+    Ok(bytes)
+}
+
+fn is_hex(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/* Prints every frame of `bytes`, returns false if some of them could not be decoded */
+fn decode_stream(bytes: &[u8], direction: Direction) -> bool {
+    let mut offset = 0;
+    let mut frames = 0;
+    let mut clean = true;
+
+    while offset < bytes.len() {
+        let (size, prefix_len) = match framing::parse_length(&bytes[offset..]) {
+            Ok(Some(length)) => length,
+            Ok(None) => {
+                println!(
+                    "{:#06x}  truncated length prefix, {} bytes left: {}",
+                    offset,
+                    bytes.len() - offset,
+                    hex::encode_spaced(&bytes[offset..])
+                );
+                clean = false;
+                break;
+            }
+            Err(_) => {
+                /* without a valid length the next frame boundary is unknown, nothing after can be decoded */
+                println!(
+                    "{:#06x}  invalid length prefix, giving up on the {} bytes left",
+                    offset,
+                    bytes.len() - offset
+                );
+                clean = false;
+                break;
+            }
+        };
+
+        let start = offset + prefix_len;
+        let available = bytes.len() - start;
+        if available < size {
+            println!(
+                "{:#06x}  truncated frame: {} bytes announced, only {} captured",
+                offset, size, available
+            );
+            clean = false;
+            break;
+        }
+
+        frames += 1;
+        let payload = &bytes[start..start + size];
+        let note = if size > framing::DEFAULT_MAX_FRAME_SIZE {
+            " (above the default max_frame_size)"
+        } else {
+            ""
+        };
+        println!("{:#06x}  frame {}, {} bytes{}", offset, frames, size, note);
+        match decode_message(payload, direction) {
+            Ok(json) => println!("        {}", json),
+            Err(e) => {
+                println!("        decode error: {}", e);
+                println!("        payload: {}", hex::encode_spaced(payload));
+                clean = false;
+            }
+        }
+        offset = start + size;
+    }
+
+    println!("{} frames, {} bytes", frames, bytes.len());
+    clean
+}
+
+fn decode_message(payload: &[u8], direction: Direction) -> Result<String, prost::DecodeError> {
+    let json = match direction {
+        Direction::Client => serde_json::to_string(&ClientMessage::decode(payload)?),
+        Direction::Server => serde_json::to_string(&ServerMessage::decode(payload)?),
+    };
+    Ok(json.expect("messages are serializable"))
+}
@@ -0,0 +1,651 @@
+/*
+    Client of the server over TCP.
+
+        let mut client = Client::connect("127.0.0.1:8080")?;
+        assert_eq!(client.echo("hello")?, "hello");
+        assert_eq!(client.add(1, 2)?, 3);
+
+    A request is sent as one frame and the client blocks until its response arrived, the
+    framing is the one of the server (see the framing module). An ErrorResponse from the
+    server is returned as ClientError::Server. Nothing is printed, errors are returned.
+*/
+use crate::{
+    framing::{self, ClosedBetweenFrames, FrameError},
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, ErrorCode, HealthCheck, HealthResponse,
+        ServerMessage, StatsRequest, StatsResponse,
+    },
+};
+use prost::Message;
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/* Head start of a connection attempt over the next one, the delay RFC 8305 recommends */
+pub const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/* Settings of a client connection, a timeout set to None is disabled */
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /* how long connecting to one address may take */
+    pub connect_timeout: Option<Duration>,
+    /*
+        when the server address resolves to several addresses, how long an attempt is given
+        before the next address is tried in parallel (see connect_any)
+    */
+    pub attempt_delay: Duration,
+    /* how long waiting for a response may take */
+    pub read_timeout: Option<Duration>,
+    /* how long sending a request may take */
+    pub write_timeout: Option<Duration>,
+    /*
+        deadline of every request, from sending it to having its response. It is sent along
+        with the request so the server skips it once the deadline passed (see send_within).
+    */
+    pub request_timeout: Option<Duration>,
+    /* largest response frame accepted */
+    pub max_frame_size: usize,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            connect_timeout: Some(Duration::from_secs(5)),
+            attempt_delay: DEFAULT_ATTEMPT_DELAY,
+            read_timeout: Some(Duration::from_secs(30)),
+            write_timeout: Some(Duration::from_secs(30)),
+            request_timeout: None,
+            max_frame_size: framing::DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+/* Why a request did not get the answer it expected */
+#[derive(Debug)]
+pub enum ClientError {
+    /* connecting, sending the request or reading the response failed */
+    Io(io::Error),
+    /* the connection was reset, or closed in the middle of a response */
+    Closed(io::Error),
+    /*
+        the server closed the connection cleanly instead of answering: it is shutting down, or
+        it dropped the connection on purpose (idle for longer than its read timeout, above its
+        max_connections). The request was not served.
+    */
+    Shutdown(io::Error),
+    /* connecting, sending the request or waiting for the response took too long */
+    Timeout(io::Error),
+    /* the response can't be split into frames, or is larger than max_frame_size */
+    Frame(FrameError),
+    /* the response is not a valid ServerMessage */
+    Decode(prost::DecodeError),
+    /* the server answered with an ErrorResponse */
+    Server { code: ErrorCode, message: String },
+    /* the server answered with a message that does not belong to the request */
+    UnexpectedResponse(ServerMessage),
+    /* no pooled connection became available within the checkout timeout */
+    CheckoutTimeout(Duration),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "connection error: {}", e),
+            ClientError::Closed(e) => write!(f, "connection closed: {}", e),
+            ClientError::Shutdown(e) => write!(f, "the server closed the connection: {}", e),
+            ClientError::Timeout(e) => write!(f, "timed out: {}", e),
+            ClientError::Frame(e) => write!(f, "invalid response frame: {}", e),
+            ClientError::Decode(e) => write!(f, "invalid response: {}", e),
+            ClientError::Server { code, message } => write!(f, "server error {}: {}", code.as_str_name(), message),
+            ClientError::UnexpectedResponse(response) => write!(f, "unexpected response: {:?}", response),
+            ClientError::CheckoutTimeout(timeout) => write!(f, "no connection available after {:?}", timeout),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(e) | ClientError::Closed(e) | ClientError::Shutdown(e) | ClientError::Timeout(e) => Some(e),
+            ClientError::Frame(e) => Some(e),
+            ClientError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/*
+    Sorts the I/O errors by cause: a framing error, a timeout, the server closing the connection
+    (cleanly or not) or anything else
+*/
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<FrameError>()) {
+            let inner = e.into_inner().expect("checked above");
+            return ClientError::Frame(*inner.downcast::<FrameError>().expect("checked above"));
+        }
+        if e.get_ref().is_some_and(|inner| inner.is::<ClosedBetweenFrames>()) {
+            return ClientError::Shutdown(e);
+        }
+        match e.kind() {
+            /* a socket read or write timeout shows up as WouldBlock on unix */
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => ClientError::Timeout(e),
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => ClientError::Closed(e),
+            _ => ClientError::Io(e),
+        }
+    }
+}
+
+impl From<FrameError> for ClientError {
+    fn from(e: FrameError) -> Self {
+        ClientError::Frame(e)
+    }
+}
+
+impl From<prost::DecodeError> for ClientError {
+    fn from(e: prost::DecodeError) -> Self {
+        ClientError::Decode(e)
+    }
+}
+
+impl ClientError {
+    /*
+        True when the connection can't be used for another request: the error happened while
+        sending or receiving, so the stream may be in the middle of a frame.
+    */
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            ClientError::Io(_)
+                | ClientError::Closed(_)
+                | ClientError::Shutdown(_)
+                | ClientError::Timeout(_)
+                | ClientError::Frame(_)
+                | ClientError::Decode(_)
+        )
+    }
+}
+
+/* A connection to a server */
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+    config: ClientConfig,
+}
+
+impl Client {
+    /* Connects to any of the addresses `addr` resolves to, see connect_any */
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Client, ClientError> {
+        Client::with_config(addr, ClientConfig::default())
+    }
+
+    /* Connects applying the timeouts and limits of `config` */
+    pub fn with_config(addr: impl ToSocketAddrs, config: ClientConfig) -> Result<Client, ClientError> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        let stream = connect_any(&addrs, config.connect_timeout, config.attempt_delay)?;
+        Client::from_stream(stream, config)
+    }
+
+    /* Uses an already connected stream */
+    pub fn from_stream(stream: TcpStream, config: ClientConfig) -> Result<Client, ClientError> {
+        stream.set_read_timeout(config.read_timeout)?;
+        stream.set_write_timeout(config.write_timeout)?;
+        stream.set_nodelay(true)?;
+        Ok(Client { stream, config })
+    }
+
+    /* Returns the address of the server */
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /* Sends `content` in an EchoMessage and returns the content echoed back */
+    pub fn echo(&mut self, content: &str) -> Result<String, ClientError> {
+        let request = client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        });
+        match self.call(request)? {
+            server_message::Message::EchoMessage(echo) => Ok(echo.content),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /*
+        Checks that the server still answers on this connection with a HealthCheck, which the
+        server counts as "health_check" and not as a request of a service. Any answer counts,
+        an ErrorResponse (a server that predates HealthCheck) included.
+    */
+    pub fn ping(&mut self) -> Result<(), ClientError> {
+        match self.health() {
+            Err(e) if e.is_connection_error() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /* Asks the server for `a + b` */
+    pub fn add(&mut self, a: i32, b: i32) -> Result<i32, ClientError> {
+        match self.call(client_message::Message::AddRequest(AddRequest { a, b }))? {
+            server_message::Message::AddResponse(add) => Ok(add.result),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /* Asks the server for its uptime, version and counters */
+    pub fn stats(&mut self) -> Result<StatsResponse, ClientError> {
+        match self.call(client_message::Message::StatsRequest(StatsRequest {}))? {
+            server_message::Message::StatsResponse(stats) => Ok(stats),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /* Asks the server whether it is live and ready to take more clients */
+    pub fn health(&mut self) -> Result<HealthResponse, ClientError> {
+        match self.call(client_message::Message::HealthCheck(HealthCheck {}))? {
+            server_message::Message::HealthResponse(health) => Ok(health),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /*
+        Sends any request and returns the response, an ErrorResponse is turned into
+        ClientError::Server.
+    */
+    pub fn call(&mut self, request: client_message::Message) -> Result<server_message::Message, ClientError> {
+        into_response(self.send(ClientMessage { message: Some(request), ..Default::default() })?)
+    }
+
+    /* Like call, with a deadline of `timeout` for this request (see send_within) */
+    pub fn call_within(
+        &mut self,
+        request: client_message::Message,
+        timeout: Duration,
+    ) -> Result<server_message::Message, ClientError> {
+        into_response(self.send_within(ClientMessage { message: Some(request), ..Default::default() }, timeout)?)
+    }
+
+    /*
+        Sends a ClientMessage as it is and returns the ServerMessage answering it, whatever it
+        is. The request_timeout of the config, if any, is its deadline.
+    */
+    pub fn send(&mut self, request: ClientMessage) -> Result<ServerMessage, ClientError> {
+        if let Some(timeout) = self.config.request_timeout {
+            return self.send_within(request, timeout);
+        }
+        self.stream.write_all(&framing::encode_frame(&request))?;
+        let frame = framing::read_frame(&mut self.stream, self.config.max_frame_size)?;
+        Ok(ServerMessage::decode(frame.as_slice())?)
+    }
+
+    /*
+        Sends a request that must be answered within `timeout`. The deadline goes along with the
+        request (`deadline_unix_ms`), a server getting to it too late answers DEADLINE_EXCEEDED
+        instead of serving it. The client stops waiting at the deadline with
+        ClientError::Timeout, the response may still come later so the connection is not
+        usable anymore then.
+    */
+    pub fn send_within(&mut self, mut request: ClientMessage, timeout: Duration) -> Result<ServerMessage, ClientError> {
+        let deadline = Instant::now() + timeout;
+        request.deadline_unix_ms = (SystemTime::now() + timeout)
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut stream = DeadlineStream {
+            stream: &self.stream,
+            config: &self.config,
+            deadline,
+        };
+        let result = stream
+            .write_all(&framing::encode_frame(&request))
+            .and_then(|_| framing::read_frame(&mut stream, self.config.max_frame_size));
+        /* back to the timeouts of the config for the next requests */
+        self.stream.set_read_timeout(self.config.read_timeout)?;
+        self.stream.set_write_timeout(self.config.write_timeout)?;
+        Ok(ServerMessage::decode(result?.as_slice())?)
+    }
+
+    /* Closes the connection */
+    pub fn close(self) -> Result<(), ClientError> {
+        match self.stream.shutdown(Shutdown::Both) {
+            /* the server may have closed it already */
+            Err(e) if e.kind() != io::ErrorKind::NotConnected => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/* The stream of a Client whose reads and writes give up at `deadline` */
+struct DeadlineStream<'a> {
+    stream: &'a TcpStream,
+    config: &'a ClientConfig,
+    deadline: Instant,
+}
+
+impl DeadlineStream<'_> {
+    /* The time left before the deadline, capped by the timeout of the config */
+    fn timeout(&self, limit: Option<Duration>) -> io::Result<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the deadline of the request passed"));
+        }
+        Ok(limit.map_or(remaining, |limit| limit.min(remaining)))
+    }
+}
+
+impl Read for DeadlineStream<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.timeout(self.config.read_timeout)?))?;
+        self.stream.read(buffer)
+    }
+}
+
+impl Write for DeadlineStream<'_> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.timeout(self.config.write_timeout)?))?;
+        self.stream.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/* Every connection attempt made by connect_any and why it failed */
+#[derive(Debug)]
+pub struct ConnectError {
+    pub attempts: Vec<(SocketAddr, io::Error)>,
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not connect to any address")?;
+        for (index, (addr, error)) in self.attempts.iter().enumerate() {
+            write!(f, "{} {} ({})", if index == 0 { ":" } else { "," }, addr, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+/*
+    Connects to the first of `addrs` that accepts the connection, the "happy eyeballs" way
+    (RFC 8305): the addresses are tried alternating between IPv6 and IPv4, starting with the
+    family of the first one, and each attempt is given `attempt_delay` before the next one is
+    started next to it, or less if it fails sooner. A host whose first address is unreachable
+    (an IPv6 address while the server listens on IPv4 only) costs `attempt_delay` instead of a
+    whole connect timeout.
+
+    When no address accepts, the error holds a ConnectError listing every attempt.
+*/
+pub fn connect_any(addrs: &[SocketAddr], timeout: Option<Duration>, attempt_delay: Duration) -> io::Result<TcpStream> {
+    let addrs = interleave_families(addrs);
+    if addrs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the address does not resolve"));
+    }
+    if let [addr] = addrs.as_slice() {
+        return connect_to(addr, timeout).map_err(|e| connect_error(vec![(*addr, e)]));
+    }
+
+    let (results, attempts) = mpsc::channel();
+    let mut failures = Vec::new();
+    let mut started = 0;
+    let mut next_start = Instant::now();
+    while failures.len() < addrs.len() {
+        if started < addrs.len() && Instant::now() >= next_start {
+            let (addr, results) = (addrs[started], results.clone());
+            /* a stream connected after another attempt won is dropped by the failing send */
+            thread::spawn(move || {
+                let _ = results.send((addr, connect_to(&addr, timeout)));
+            });
+            started += 1;
+            next_start = Instant::now() + attempt_delay;
+        }
+
+        let result = if started < addrs.len() {
+            match attempts.recv_timeout(next_start.saturating_duration_since(Instant::now())) {
+                Ok(result) => result,
+                Err(_) => continue,
+            }
+        } else {
+            attempts.recv().expect("every attempt sends its result")
+        };
+        match result {
+            (_, Ok(stream)) => return Ok(stream),
+            (addr, Err(e)) => {
+                failures.push((addr, e));
+                /* no reason to wait for the delay, start the next attempt now */
+                next_start = Instant::now();
+            }
+        }
+    }
+    Err(connect_error(failures))
+}
+
+pub(crate) fn connect_error(attempts: Vec<(SocketAddr, io::Error)>) -> io::Error {
+    let kind = attempts.last().map_or(io::ErrorKind::Other, |(_, e)| e.kind());
+    io::Error::new(kind, ConnectError { attempts })
+}
+
+/* Orders the addresses alternating the families, starting with the family of the first one */
+pub(crate) fn interleave_families(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (mut first, mut second): (VecDeque<SocketAddr>, VecDeque<SocketAddr>) =
+        addrs.iter().partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut ordered = Vec::with_capacity(addrs.len());
+    while !first.is_empty() || !second.is_empty() {
+        ordered.extend(first.pop_front());
+        ordered.extend(second.pop_front());
+    }
+    ordered
+}
+
+fn connect_to(addr: &SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    match timeout {
+        Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+        None => TcpStream::connect(addr),
+    }
+}
+
+/* Unwraps the message answering a call, turning an ErrorResponse into ClientError::Server */
+pub(crate) fn into_response(response: ServerMessage) -> Result<server_message::Message, ClientError> {
+    match response.message {
+        Some(server_message::Message::ErrorResponse(error)) => Err(ClientError::Server {
+            code: ErrorCode::try_from(error.code).unwrap_or(ErrorCode::Unspecified),
+            message: error.message,
+        }),
+        Some(response) => Ok(response),
+        None => Err(unexpected(None)),
+    }
+}
+
+pub(crate) fn unexpected(response: Option<server_message::Message>) -> ClientError {
+    ClientError::UnexpectedResponse(ServerMessage { message: response })
+}
+
+/*
+    How a ReconnectingClient waits between two attempts. The n-th wait is
+    initial * multiplier^(n-1), capped at `max`, of which a random half is taken off (jitter)
+    so that clients losing the same server do not all come back at the same time.
+*/
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    /* attempts (the first one included) made for one request before giving up */
+    pub max_attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(5),
+            multiplier: 2.0,
+            max_attempts: 8,
+        }
+    }
+}
+
+impl Backoff {
+    /* The wait before the attempt following the `failures`-th failure, without jitter */
+    pub fn delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(64) as i32;
+        let delay = self.initial.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::from_secs_f64(delay.min(self.max.as_secs_f64()))
+    }
+}
+
+/*
+    A client that survives the server going away: a request failing because of the connection
+    is sent again on a new connection, waiting between the attempts as set by `backoff`. Only
+    idempotent requests are sent again, which all the requests of the protocol are so far.
+    The errors answered by the server (ClientError::Server) are returned right away.
+*/
+#[derive(Debug)]
+pub struct ReconnectingClient {
+    addrs: Vec<SocketAddr>,
+    config: ClientConfig,
+    backoff: Backoff,
+    client: Option<Client>,
+    opened: bool,
+    reconnects: u64,
+    jitter: Jitter,
+}
+
+impl ReconnectingClient {
+    /*
+        Resolves `addr` once, the connection itself is only opened by the first request so a
+        server that is not up yet is not an error here.
+    */
+    pub fn new(addr: impl ToSocketAddrs, config: ClientConfig, backoff: Backoff) -> Result<Self, ClientError> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the address does not resolve").into());
+        }
+        Ok(ReconnectingClient {
+            addrs,
+            config,
+            backoff,
+            client: None,
+            opened: false,
+            reconnects: 0,
+            jitter: Jitter::new(),
+        })
+    }
+
+    /* How many times a new connection was opened after the first one */
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+
+    /* True while a connection is open (it may still turn out to be broken on the next request) */
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn echo(&mut self, content: &str) -> Result<String, ClientError> {
+        let request = client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        });
+        match self.call(request)? {
+            server_message::Message::EchoMessage(echo) => Ok(echo.content),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    pub fn add(&mut self, a: i32, b: i32) -> Result<i32, ClientError> {
+        match self.call(client_message::Message::AddRequest(AddRequest { a, b }))? {
+            server_message::Message::AddResponse(add) => Ok(add.result),
+            other => Err(unexpected(Some(other))),
+        }
+    }
+
+    /* Like Client::call, reconnecting and sending the request again when the connection breaks */
+    pub fn call(&mut self, request: client_message::Message) -> Result<server_message::Message, ClientError> {
+        let mut failures = 0;
+        loop {
+            let error = match self.connected() {
+                Ok(client) => match client.call(request.clone()) {
+                    Ok(response) => return Ok(response),
+                    Err(e) if e.is_connection_error() => {
+                        /* the stream may be in the middle of a frame, never use it again */
+                        self.client = None;
+                        if !is_idempotent(&request) {
+                            return Err(e);
+                        }
+                        e
+                    }
+                    Err(e) => return Err(e),
+                },
+                Err(e) => e,
+            };
+
+            failures += 1;
+            if failures >= self.backoff.max_attempts {
+                return Err(error);
+            }
+            let delay = self.backoff.delay(failures);
+            thread::sleep(delay.mul_f64(0.5 + self.jitter.next() * 0.5));
+        }
+    }
+
+    fn connected(&mut self) -> Result<&mut Client, ClientError> {
+        if self.client.is_none() {
+            let client = Client::with_config(self.addrs.as_slice(), self.config.clone())?;
+            if self.opened {
+                self.reconnects += 1;
+            }
+            self.opened = true;
+            self.client = Some(client);
+        }
+        Ok(self.client.as_mut().expect("connected above"))
+    }
+}
+
+/* Requests that can be sent twice without changing the outcome */
+fn is_idempotent(request: &client_message::Message) -> bool {
+    match request {
+        client_message::Message::EchoMessage(_)
+        | client_message::Message::AddRequest(_)
+        | client_message::Message::StatsRequest(_)
+        | client_message::Message::HealthCheck(_) => true,
+    }
+}
+
+/* A small xorshift generator, good enough to spread retries without pulling a random crate */
+#[derive(Debug)]
+struct Jitter(u64);
+
+impl Jitter {
+    fn new() -> Self {
+        static INSTANCES: AtomicU64 = AtomicU64::new(0);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        /* never 0, xorshift would stay there */
+        Jitter((time ^ INSTANCES.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)) | 1)
+    }
+
+    /* A number in [0, 1) */
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,364 @@
+/*
+    Server settings loaded from a TOML file.
+
+    Every section and every key is optional, what is missing keeps its default value:
+
+        [listeners]
+        tcp = "127.0.0.1:8080"
+        websocket = "127.0.0.1:8081"
+        http = "127.0.0.1:8082"
+        metrics = "127.0.0.1:9100"
+        health = "127.0.0.1:9101"
+
+        [limits]
+        workers = 4
+        max_frame_size = 65536
+        max_connections = 100
+
+        [timeouts]
+        read_ms = 30000
+        write_ms = 5000
+        request_ms = 10000
+        drain_ms = 30000
+
+        [logging]
+        level = "info"
+
+        [services]
+        echo = true
+        add = true
+
+        [recording]
+        path = "traffic.jsonl"
+
+        [audit]
+        path = "audit.log"
+        max_size_bytes = 10485760
+        max_age_ms = 86400000
+        keep = 5
+*/
+use crate::{
+    audit::AuditConfig,
+    framing,
+    server::{ReloadReport, Server, ServerConfig, Services},
+};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path, str::FromStr, time::Duration};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listeners: Listeners,
+    pub limits: Limits,
+    pub timeouts: Timeouts,
+    pub logging: Logging,
+    pub services: Services,
+    pub recording: Recording,
+    pub audit: Audit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Listeners {
+    pub tcp: String,
+    pub websocket: Option<String>,
+    pub http: Option<String>,
+    /* Prometheus endpoint, keep it on a local address */
+    pub metrics: Option<String>,
+    /* /livez and /readyz probes for the supervisor */
+    pub health: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub workers: usize,
+    pub max_frame_size: usize,
+    pub max_connections: Option<usize>,
+}
+
+/* All durations are in milliseconds, a missing timeout is disabled */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    pub read_ms: Option<u64>,
+    pub write_ms: Option<u64>,
+    pub request_ms: Option<u64>,
+    pub drain_ms: u64,
+}
+
+/* Where the TCP traffic is recorded, nothing is recorded without a path */
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Recording {
+    pub path: Option<String>,
+}
+
+/*
+    Where the requests are audited, nothing is audited without a path. The file is rotated
+    when it would grow over `max_size_bytes` or after `max_age_ms`, `keep` rotated files are kept.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Audit {
+    pub path: Option<String>,
+    pub max_size_bytes: Option<u64>,
+    pub max_age_ms: Option<u64>,
+    pub keep: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    pub level: String,
+}
+
+impl Default for Listeners {
+    fn default() -> Self {
+        Listeners {
+            tcp: "127.0.0.1:8080".to_string(),
+            websocket: None,
+            http: None,
+            metrics: None,
+            health: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            workers: 0,
+            max_frame_size: framing::DEFAULT_MAX_FRAME_SIZE,
+            max_connections: None,
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            read_ms: None,
+            write_ms: None,
+            request_ms: None,
+            drain_ms: 30_000,
+        }
+    }
+}
+
+impl Default for Audit {
+    fn default() -> Self {
+        let defaults = AuditConfig::new("");
+        Audit {
+            path: None,
+            max_size_bytes: defaults.max_size,
+            max_age_ms: None,
+            keep: defaults.keep,
+        }
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Logging {
+            level: "info".to_string(),
+        }
+    }
+}
+
+/* Why a configuration can't be used */
+#[derive(Debug)]
+pub enum ConfigError {
+    /* the file could not be read */
+    Io { path: String, source: std::io::Error },
+    /* the file is not valid TOML or does not match the expected layout */
+    Parse { path: String, message: String },
+    /* a value is not acceptable, `key` is the dotted path of the offending key */
+    Invalid { key: &'static str, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "{}: {}", path, source),
+            ConfigError::Parse { path, message } => write!(f, "{}: {}", path, message),
+            ConfigError::Invalid { key, message } => write!(f, "{}: {}", key, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /* Reads, parses and validates the TOML file at `path` */
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Config::parse(&text, &path.display().to_string())
+    }
+
+    /* Parses and validates a TOML document, `origin` names it in the errors */
+    pub fn parse(text: &str, origin: &str) -> Result<Config, ConfigError> {
+        let config: Config = toml::from_str(text).map_err(|e| ConfigError::Parse {
+            path: origin.to_string(),
+            message: e.to_string(),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /* Checks the values that the TOML types alone can't enforce */
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_address("listeners.tcp", Some(&self.listeners.tcp))?;
+        check_address("listeners.websocket", self.listeners.websocket.as_deref())?;
+        check_address("listeners.http", self.listeners.http.as_deref())?;
+        check_address("listeners.metrics", self.listeners.metrics.as_deref())?;
+        check_address("listeners.health", self.listeners.health.as_deref())?;
+
+        if self.limits.max_frame_size == 0 {
+            return Err(invalid("limits.max_frame_size", "must be greater than 0"));
+        }
+        if self.limits.max_connections == Some(0) {
+            return Err(invalid("limits.max_connections", "must be greater than 0"));
+        }
+
+        for (key, value) in [
+            ("timeouts.read_ms", self.timeouts.read_ms),
+            ("timeouts.write_ms", self.timeouts.write_ms),
+            ("timeouts.request_ms", self.timeouts.request_ms),
+        ] {
+            if value == Some(0) {
+                return Err(invalid(key, "must be greater than 0, leave it out to disable the timeout"));
+            }
+        }
+
+        if self.recording.path.as_deref() == Some("") {
+            return Err(invalid("recording.path", "must not be empty, leave it out to disable the recording"));
+        }
+
+        if self.audit.path.as_deref() == Some("") {
+            return Err(invalid("audit.path", "must not be empty, leave it out to disable the audit log"));
+        }
+        if self.audit.max_size_bytes == Some(0) {
+            return Err(invalid("audit.max_size_bytes", "must be greater than 0, leave it out to disable the limit"));
+        }
+        if self.audit.max_age_ms == Some(0) {
+            return Err(invalid("audit.max_age_ms", "must be greater than 0, leave it out to disable the limit"));
+        }
+
+        if LevelFilter::from_str(&self.logging.level).is_err() {
+            return Err(ConfigError::Invalid {
+                key: "logging.level",
+                message: format!(
+                    "unknown level `{}`, expected off, error, warn, info, debug or trace",
+                    self.logging.level
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /* The settings the server applies to its connections */
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig {
+            read_timeout: self.timeouts.read_ms.map(Duration::from_millis),
+            write_timeout: self.timeouts.write_ms.map(Duration::from_millis),
+            request_timeout: self.timeouts.request_ms.map(Duration::from_millis),
+            max_frame_size: self.limits.max_frame_size,
+            drain_timeout: Some(Duration::from_millis(self.timeouts.drain_ms)),
+            workers: self.limits.workers,
+            max_connections: self.limits.max_connections,
+            services: self.services.clone(),
+        }
+    }
+
+    /* Where and how the requests are audited, None when the audit log is disabled */
+    pub fn audit_config(&self) -> Option<AuditConfig> {
+        let path = self.audit.path.as_ref()?;
+        Some(AuditConfig {
+            max_size: self.audit.max_size_bytes,
+            max_age: self.audit.max_age_ms.map(Duration::from_millis),
+            keep: self.audit.keep,
+            ..AuditConfig::new(path)
+        })
+    }
+
+    /* The log level, `validate` made sure it parses */
+    pub fn log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.logging.level).unwrap_or(LevelFilter::Info)
+    }
+
+    /*
+        Applies `self`, read again, to `server` running with `running`: the server settings
+        through `Server::reload` and the log level to the logger, both reported in `applied`
+        ("logging.level" for the latter). The listeners, the recording and the audit log are
+        only set up when the server starts, a change of them is reported in `restart_required`
+        under its key ("listeners.tcp") and keeps its current value. `running` is updated with
+        what is now in effect.
+    */
+    pub fn reload(mut self, server: &Server, running: &mut Config) -> ReloadReport {
+        let mut report = server.reload(self.server_config());
+        if self.log_level() != running.log_level() {
+            log::set_max_level(self.log_level());
+            report.applied.push("logging.level");
+        }
+        for (key, changed) in [
+            ("listeners.tcp", self.listeners.tcp != running.listeners.tcp),
+            ("listeners.websocket", self.listeners.websocket != running.listeners.websocket),
+            ("listeners.http", self.listeners.http != running.listeners.http),
+            ("listeners.metrics", self.listeners.metrics != running.listeners.metrics),
+            ("listeners.health", self.listeners.health != running.listeners.health),
+            ("recording", self.recording != running.recording),
+            ("audit", self.audit != running.audit),
+        ] {
+            if changed {
+                report.restart_required.push(key);
+            }
+        }
+
+        self.listeners = running.listeners.clone();
+        self.recording = running.recording.clone();
+        self.audit = running.audit.clone();
+        self.limits.workers = running.limits.workers;
+        *running = self;
+        report
+    }
+
+    /* The configuration written back as TOML */
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+fn invalid(key: &'static str, message: &str) -> ConfigError {
+    ConfigError::Invalid {
+        key,
+        message: message.to_string(),
+    }
+}
+
+/* A listener address must look like `host:port` */
+fn check_address(key: &'static str, addr: Option<&str>) -> Result<(), ConfigError> {
+    let addr = match addr {
+        Some(addr) => addr,
+        None => return Ok(()),
+    };
+    match addr.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err(ConfigError::Invalid {
+            key,
+            message: format!("`{}` is not a valid address, expected host:port", addr),
+        }),
+    }
+}
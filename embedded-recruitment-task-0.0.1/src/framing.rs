@@ -0,0 +1,181 @@
+/*
+    Framing of the messages exchanged over a byte stream (TCP).
+
+    Every message is sent as a frame: its length encoded as a protobuf varint followed by the
+    encoded message itself (what prost calls "length delimited"). The length prefix is what lets
+    the receiver know where a message ends when it arrives in several pieces, or when several
+    messages arrive in one read.
+*/
+use prost::Message;
+use std::{
+    fmt,
+    io::{self, Read},
+};
+
+/* Largest frame accepted by default (64 KiB) */
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/* A varint length prefix is never longer than this */
+const MAX_PREFIX_LEN: usize = 10;
+
+/* Why the bytes received can't be split into frames */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /* the length prefix is not a valid varint */
+    InvalidLength,
+    /* the frame announces more bytes than we are willing to buffer */
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::InvalidLength => write!(f, "invalid frame length prefix"),
+            FrameError::TooLarge { size, max } => {
+                write!(f, "frame of {} bytes exceeds the maximum of {} bytes", size, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<FrameError> for io::Error {
+    fn from(e: FrameError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/*
+    The peer closed the connection cleanly after a whole frame, before the first byte of the
+    next one. The source of the UnexpectedEof error `read_frame` returns then, a connection
+    closed in the middle of a frame is a plain UnexpectedEof.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedBetweenFrames;
+
+impl fmt::Display for ClosedBetweenFrames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connection closed by the peer between two frames")
+    }
+}
+
+impl std::error::Error for ClosedBetweenFrames {}
+
+impl From<ClosedBetweenFrames> for io::Error {
+    fn from(e: ClosedBetweenFrames) -> Self {
+        io::Error::new(io::ErrorKind::UnexpectedEof, e)
+    }
+}
+
+/* Encodes `message` as one frame */
+pub fn encode_frame<M: Message>(message: &M) -> Vec<u8> {
+    message.encode_length_delimited_to_vec()
+}
+
+/*
+    Accumulates the bytes read from a stream and hands out complete frames.
+    Bytes of a frame that is not complete yet are kept until the rest arrives.
+*/
+#[derive(Debug)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    max_frame_size: usize,
+}
+
+impl FrameDecoder {
+    pub fn new(max_frame_size: usize) -> Self {
+        FrameDecoder {
+            buffer: Vec::new(),
+            max_frame_size,
+        }
+    }
+
+    /* Changes the largest frame accepted from now on */
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    /* Appends bytes freshly read from the stream */
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /* True when some bytes of a frame were received but not the whole frame yet */
+    pub fn has_partial_frame(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /* Number of bytes waiting to be turned into frames */
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /* Removes and returns the next complete frame payload, if there is one */
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let (size, prefix_len) = match parse_length(&self.buffer)? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        if size > self.max_frame_size {
+            return Err(FrameError::TooLarge {
+                size,
+                max: self.max_frame_size,
+            });
+        }
+        if self.buffer.len() < prefix_len + size {
+            return Ok(None);
+        }
+        let frame = self.buffer[prefix_len..prefix_len + size].to_vec();
+        self.buffer.drain(..prefix_len + size);
+        Ok(Some(frame))
+    }
+}
+
+/*
+    Parses the varint length prefix at the start of `bytes`.
+    Returns the announced frame size and the prefix length, or None if the prefix is incomplete.
+*/
+pub fn parse_length(bytes: &[u8]) -> Result<Option<(usize, usize)>, FrameError> {
+    let mut size: u64 = 0;
+    for (index, byte) in bytes.iter().take(MAX_PREFIX_LEN).enumerate() {
+        size |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            let size = usize::try_from(size).map_err(|_| FrameError::InvalidLength)?;
+            return Ok(Some((size, index + 1)));
+        }
+    }
+    if bytes.len() >= MAX_PREFIX_LEN {
+        Err(FrameError::InvalidLength)
+    } else {
+        Ok(None)
+    }
+}
+
+/* Blocks until one whole frame was read from `reader` and returns its payload */
+pub fn read_frame<R: Read>(reader: &mut R, max_frame_size: usize) -> io::Result<Vec<u8>> {
+    let mut decoder = FrameDecoder::new(max_frame_size);
+    let mut byte = [0u8; 1];
+
+    /* read the prefix byte by byte so that nothing after this frame is consumed */
+    loop {
+        if let Some((size, _)) = parse_length(&decoder.buffer)? {
+            if size > max_frame_size {
+                return Err(FrameError::TooLarge { size, max: max_frame_size }.into());
+            }
+            let mut frame = vec![0u8; size];
+            reader.read_exact(&mut frame)?;
+            return Ok(frame);
+        }
+        if reader.read(&mut byte)? == 0 {
+            if !decoder.has_partial_frame() {
+                return Err(ClosedBetweenFrames.into());
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before a whole frame was received",
+            ));
+        }
+        decoder.extend(&byte);
+    }
+}
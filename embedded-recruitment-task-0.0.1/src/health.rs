@@ -0,0 +1,95 @@
+/*
+    Liveness and readiness of the server, for the supervisor deciding whether to restart it
+    (not live) or to send it clients (ready).
+
+    The same Health is reported by `Server::health`, by the answer to a HealthCheck and by the
+    probe endpoint enabled with `Server::enable_health`:
+
+        GET /livez      200 while the server runs, 503 once it stopped
+        GET /readyz     200 while it is ready, 503 otherwise
+
+    both with the Health as JSON in the body. The endpoint keeps answering while the server
+    drains its clients, so that the supervisor sees the readiness turn false.
+*/
+use crate::{server, stats::ServerStats};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::{net::TcpListener, sync::Arc, time::Duration};
+use tiny_http::{Header, Method, Request, Response};
+
+/* How long we wait for a probe before checking whether the server stopped */
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Health {
+    /*
+        the server was not stopped yet, it may still be draining. A server whose `run` did not
+        start yet is live too: it is starting, not to be restarted
+    */
+    pub live: bool,
+    /* listening, not draining and not saturated: new clients are served right away */
+    pub ready: bool,
+    /* the TCP listener accepts new clients */
+    pub listening: bool,
+    /* a graceful shutdown is in progress */
+    pub draining: bool,
+    /* every connection slot (max_connections) is taken */
+    pub saturated: bool,
+    pub active_connections: u64,
+}
+
+/*
+    Answers the probes on `listener` until the server is stopped, that is until `run` is
+    done and not only until `stop` was called.
+*/
+pub(crate) fn serve(listener: TcpListener, id: usize, stats: Arc<ServerStats>) {
+    let http_server = match tiny_http::Server::from_listener(listener, None) {
+        Ok(http_server) => http_server,
+        Err(e) => {
+            error!("Server-{}: Failed to start the health endpoint: {}", id + 1, e);
+            return;
+        }
+    };
+
+    while stats.health().live {
+        match http_server.recv_timeout(POLL_INTERVAL) {
+            Ok(Some(request)) => handle_request(request, id, &stats),
+            Ok(None) => {}
+            Err(e) => error!("Server-{}: Error receiving health probe: {}", id + 1, e),
+        }
+    }
+    info!("Server-{}: Health endpoint stopped.", id + 1);
+}
+
+fn handle_request(request: Request, id: usize, stats: &ServerStats) {
+    let health = stats.health();
+    /* the query string is not part of the probe */
+    let path = request.url().split('?').next().unwrap_or_default();
+    let (status, body) = match (request.method(), path) {
+        (Method::Get, "/livez") => (if health.live { 200 } else { 503 }, to_json(&health)),
+        (Method::Get, "/readyz") => (if health.ready { 200 } else { 503 }, to_json(&health)),
+        (_, "/livez") | (_, "/readyz") => (405, "only GET is allowed\n".to_string()),
+        _ => (404, "not found, the probes are /livez and /readyz\n".to_string()),
+    };
+    debug!("Server-{}: Health {} {} {}", id + 1, request.method(), request.url(), status);
+
+    let content_type = if status == 200 || status == 503 { "application/json" } else { "text/plain" };
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", content_type).unwrap());
+    if let Err(e) = request.respond(response) {
+        warn!("Server-{}: Failed to answer the health probe: {}", id + 1, e);
+    }
+}
+
+fn to_json(health: &Health) -> String {
+    serde_json::to_string(health).unwrap_or_else(|_| "{}".to_string())
+}
+
+/*
+    The most clients served at once with `config`: max_connections. The workers are no limit,
+    each one serves any number of clients in turns.
+*/
+pub(crate) fn capacity(config: &server::ServerConfig) -> Option<usize> {
+    config.max_connections
+}
@@ -0,0 +1,32 @@
+/*
+    Hex encoding of raw bytes, for the recordings (`Record::frame`) and the tools printing
+    or reading frames (wiredecode, replay). Lowercase digits, two per byte.
+*/
+
+/* `[0x06, 0x0a]` as "060a" */
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/* `[0x06, 0x0a]` as "06 0a", easier to read in a terminal */
+pub fn encode_spaced(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+/* "060a" (or "060A") as `[0x06, 0x0a]`, None unless `text` is an even number of hex digits */
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| {
+            let pair = text.get(index..index + 2)?;
+            /* from_str_radix takes a sign, "+f" is not a byte */
+            if !pair.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
@@ -0,0 +1,295 @@
+use crate::{
+    audit::AuditLog,
+    logging,
+    message::{self, client_message, server_message, ErrorCode},
+    server::{self, ConnectionSlot, LiveConfig, ServerConfig},
+    stats::{ServerStats, TimeoutKind},
+};
+use log::{debug, error, info, warn};
+use serde::de::DeserializeOwned;
+use std::{
+    io::{self, Read},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Method, Request, Response};
+
+/* How long we wait for a request before checking whether the server was stopped */
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/* How long a request body may take to arrive when no read_timeout is configured */
+const BODY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*
+    Serves the HTTP/JSON gateway on `listener` until `running` is cleared (the server is stopped).
+    `POST /echo` takes an EchoMessage and `POST /add` an AddRequest, both as JSON, `GET /stats`
+    sends a StatsRequest. The answer is the JSON form of the ServerMessage built by
+    `server::dispatch`.
+
+    Every request is handled on a thread of its own so a slow client does not hold up the
+    others. A request takes a connection slot while it is handled, over max_connections it is
+    answered with 503. The threads still running when the server is stopped are waited for,
+    none of them waits for a body for long (see read_body).
+*/
+pub(crate) fn serve(
+    listener: TcpListener,
+    id: usize,
+    running: &Arc<AtomicBool>,
+    config: Arc<LiveConfig>,
+    stats: Arc<ServerStats>,
+    audit: Option<Arc<AuditLog>>,
+) {
+    let http_server = match tiny_http::Server::from_listener(listener, None) {
+        Ok(http_server) => http_server,
+        Err(e) => {
+            error!("Server-{}: Failed to start the HTTP gateway: {}", id + 1, e);
+            return;
+        }
+    };
+
+    let mut handlers: Vec<JoinHandle<()>> = Vec::new();
+    while running.load(Ordering::SeqCst) {
+        match http_server.recv_timeout(POLL_INTERVAL) {
+            Ok(Some(request)) => {
+                let slot = match server::admit(id, &peer(&request), &config, &stats) {
+                    Some(slot) => slot,
+                    None => {
+                        let response = server::error_response(ErrorCode::Unspecified, "too many clients".to_string());
+                        respond(request, id, 503, &response);
+                        continue;
+                    }
+                };
+                let config = config.get();
+                let stats = Arc::clone(&stats);
+                let audit = audit.clone();
+                let running = Arc::clone(running);
+                handlers.push(thread::spawn(move || {
+                    handle_request(request, slot, id, &config, &stats, audit.as_ref(), &running)
+                }));
+            }
+            Ok(None) => {}
+            Err(e) => error!("Server-{}: Error receiving HTTP request: {}", id + 1, e),
+        }
+        /* forget the requests that are answered already */
+        handlers.retain(|handler| !handler.is_finished());
+    }
+    for handler in handlers {
+        if handler.join().is_err() {
+            error!("Server-{}: An HTTP request handler panicked.", id + 1);
+        }
+    }
+    info!("Server-{}: HTTP gateway stopped.", id + 1);
+}
+
+fn handle_request(
+    mut request: Request,
+    mut slot: ConnectionSlot,
+    id: usize,
+    config: &ServerConfig,
+    stats: &ServerStats,
+    audit: Option<&Arc<AuditLog>>,
+    running: &AtomicBool,
+) {
+    let services = &config.services;
+    /* every request is a connection of its own in the logs and the audit log */
+    let peer = peer(&request);
+    let span = logging::connection_span(&peer);
+    let request_bytes = request.body_length().unwrap_or(0);
+    debug!("Server-{}: HTTP {} {}", id + 1, request.method(), request.url());
+    let started = Instant::now();
+    /* the query string is not part of the endpoint */
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    let message_type = match path.as_str() {
+        "/echo" => "echo_message",
+        "/add" => "add_request",
+        "/stats" => "stats_request",
+        _ => "invalid",
+    };
+
+    /* the POST endpoints need their body first */
+    let mut body = Err(io::ErrorKind::UnexpectedEof.into());
+    if request.method() == &Method::Post && (path == "/echo" || path == "/add") {
+        match read_body(request, slot, config, running) {
+            Some((read_request, read_slot, read_body)) => {
+                (request, slot, body) = (read_request, read_slot, read_body);
+            }
+            None => {
+                warn!("Server-{}: HTTP client {} did not send its body in time.", id + 1, peer);
+                stats.record_timeout(TimeoutKind::Read);
+                return;
+            }
+        }
+    }
+
+    let (status, server_message) = match (request.method(), path.as_str()) {
+        (Method::Post, "/echo") => {
+            call::<message::EchoMessage>(body, id, config, stats, client_message::Message::EchoMessage)
+        }
+        (Method::Post, "/add") => {
+            call::<message::AddRequest>(body, id, config, stats, client_message::Message::AddRequest)
+        }
+        (Method::Get, "/stats") => {
+            let request = message::ClientMessage {
+                message: Some(client_message::Message::StatsRequest(message::StatsRequest {})),
+                ..Default::default()
+            };
+            let server_message = server::dispatch(request, id, services, stats);
+            (status_code(&server_message), server_message)
+        }
+        (_, "/echo") | (_, "/add") => (
+            405,
+            server::error_response(ErrorCode::InvalidRequest, "only POST is allowed".to_string()),
+        ),
+        (_, "/stats") => (
+            405,
+            server::error_response(ErrorCode::InvalidRequest, "only GET is allowed".to_string()),
+        ),
+        (_, path) => (
+            404,
+            server::error_response(ErrorCode::InvalidRequest, format!("no such endpoint: {}", path)),
+        ),
+    };
+
+    let latency = started.elapsed();
+    stats.record_request(message_type, server_message.outcome(), latency);
+    if let Some(audit) = audit {
+        audit
+            .connection(span.id(), &peer)
+            .request(message_type, server_message.outcome(), latency, request_bytes);
+    }
+
+    respond(request, id, status, &server_message);
+    /* answered, the slot is free for the next client */
+    drop(slot);
+}
+
+/* The body of a request as read_body got it, with the request and the slot it gives back */
+type ReadBody = (Request, ConnectionSlot, io::Result<Vec<u8>>);
+
+/*
+    Reads the body of `request`, at most max_frame_size + 1 bytes so that call can tell an
+    oversized one. tiny_http keeps the socket to itself and no read timeout can be set on it,
+    so the body is read on a thread of its own that we wait for up to read_timeout (BODY_TIMEOUT
+    without one), and at most DRAIN_STALL_LIMIT once the server is stopped. None when we gave
+    up: the request is left to that thread with its slot until the client sends the rest or
+    leaves, so max_connections still bounds how many stalled clients there can be. Bodies up to
+    1024 bytes are read by tiny_http itself before the request is handed over.
+*/
+fn read_body(
+    mut request: Request,
+    slot: ConnectionSlot,
+    config: &ServerConfig,
+    running: &AtomicBool,
+) -> Option<ReadBody> {
+    let limit = config.max_frame_size as u64 + 1;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut body = Vec::new();
+        let read = request.as_reader().take(limit).read_to_end(&mut body).map(|_| body);
+        /* nobody waits for it when it came too late */
+        let _ = sender.send((request, slot, read));
+    });
+
+    let deadline = Instant::now() + config.read_timeout.unwrap_or(BODY_TIMEOUT);
+    let mut stopped_at = None;
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(read) => return Some(read),
+            Err(RecvTimeoutError::Disconnected) => return None,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if !running.load(Ordering::SeqCst) && stopped_at.is_none() {
+            stopped_at = Some(Instant::now());
+        }
+        let stalled = stopped_at.is_some_and(|stopped_at| stopped_at.elapsed() >= server::DRAIN_STALL_LIMIT);
+        if stalled || Instant::now() >= deadline {
+            return None;
+        }
+    }
+}
+
+/* Sends `server_message` as the JSON answer to `request` */
+fn respond(request: Request, id: usize, status: u16, server_message: &message::ServerMessage) {
+    let body = serde_json::to_string(server_message).unwrap_or_else(|_| "{}".to_string());
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    if let Err(e) = request.respond(response) {
+        warn!("Server-{}: Failed to send HTTP response: {}", id + 1, e);
+    }
+}
+
+fn peer(request: &Request) -> String {
+    request.remote_addr().map_or_else(|| "unknown".to_string(), |addr| addr.to_string())
+}
+
+/*
+    Decodes the JSON body as `T`, wraps it in a ClientMessage and runs it through the dispatcher.
+    A body is limited to max_frame_size like a TCP request (see read_body).
+*/
+fn call<T: DeserializeOwned>(
+    body: io::Result<Vec<u8>>,
+    id: usize,
+    config: &ServerConfig,
+    stats: &ServerStats,
+    wrap: fn(T) -> client_message::Message,
+) -> (u16, message::ServerMessage) {
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+            400,
+                server::error_response(ErrorCode::InvalidRequest, format!("failed to read the body: {}", e)),
+            )
+        }
+    };
+    if body.len() > config.max_frame_size {
+        let response = server::error_response(
+            ErrorCode::FrameTooLarge,
+            format!("the body is larger than {} bytes", config.max_frame_size),
+        );
+        return (status_code(&response), response);
+    }
+    let payload: T = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            return (
+                400,
+                server::error_response(ErrorCode::InvalidRequest, format!("invalid JSON body: {}", e)),
+            )
+        }
+    };
+
+    let server_message = server::dispatch(
+        message::ClientMessage {
+            message: Some(wrap(payload)),
+            ..Default::default()
+        },
+        id,
+        &config.services,
+        stats,
+    );
+    (status_code(&server_message), server_message)
+}
+
+/* Maps the protocol level outcome of a request onto an HTTP status code */
+fn status_code(server_message: &message::ServerMessage) -> u16 {
+    match &server_message.message {
+        Some(server_message::Message::ErrorResponse(error)) => match ErrorCode::try_from(error.code) {
+            Ok(ErrorCode::InvalidRequest) => 400,
+            Ok(ErrorCode::Overflow) => 422,
+            Ok(ErrorCode::FrameTooLarge) => 413,
+            Ok(ErrorCode::ServiceDisabled) => 501,
+            Ok(ErrorCode::DeadlineExceeded) => 504,
+            _ => 500,
+        },
+        Some(_) => 200,
+        None => 500,
+    }
+}
@@ -1,6 +1,74 @@
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod audit;
+pub mod client;
+pub mod config;
+pub mod framing;
+pub mod health;
+pub mod hex;
+pub mod logging;
+pub mod pool;
+pub mod recording;
 pub mod server;
+pub mod stats;
+pub mod transport;
+mod http;
+mod metrics;
+mod websocket;
 
 pub mod message {
     include!(concat!(env!("OUT_DIR"), "/messages.rs"));
-}
 
+    impl ClientMessage {
+        /* Names the kind of request as the JSON form does ("add_request"), for the logs */
+        pub fn message_type(&self) -> &'static str {
+            match self.message {
+                Some(client_message::Message::EchoMessage(_)) => "echo_message",
+                Some(client_message::Message::AddRequest(_)) => "add_request",
+                Some(client_message::Message::StatsRequest(_)) => "stats_request",
+                Some(client_message::Message::HealthCheck(_)) => "health_check",
+                None => "empty",
+            }
+        }
+    }
+
+    impl ServerMessage {
+        /* "ok", or the name of the error code ("overflow") when the response is an ErrorResponse */
+        pub fn outcome(&self) -> &'static str {
+            let error = match &self.message {
+                Some(server_message::Message::ErrorResponse(error)) => error,
+                _ => return "ok",
+            };
+            match ErrorCode::try_from(error.code) {
+                Ok(ErrorCode::InvalidRequest) => "invalid_request",
+                Ok(ErrorCode::Overflow) => "overflow",
+                Ok(ErrorCode::FrameTooLarge) => "frame_too_large",
+                Ok(ErrorCode::ServiceDisabled) => "service_disabled",
+                Ok(ErrorCode::DeadlineExceeded) => "deadline_exceeded",
+                Ok(ErrorCode::Unspecified) | Err(_) => "unspecified",
+            }
+        }
+    }
+
+    /* Leaves the fields at their default (no deadline) out of the JSON form */
+    pub(crate) fn is_unset(value: &u64) -> bool {
+        *value == 0
+    }
+
+    /* Serializes `ErrorResponse.code` by its name ("overflow") instead of the raw number */
+    pub(crate) mod error_code {
+        use super::ErrorCode;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<S: Serializer>(code: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+            match ErrorCode::try_from(*code) {
+                Ok(code) => code.serialize(serializer),
+                Err(_) => serializer.serialize_i32(*code),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+            ErrorCode::deserialize(deserializer).map(|code| code as i32)
+        }
+    }
+}
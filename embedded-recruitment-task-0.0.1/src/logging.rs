@@ -0,0 +1,139 @@
+/*
+    Log output of the server, everything goes through the `log` macros.
+
+    Levels: error and warn for what an operator has to look at, info for the life of the
+    server (started, listening, stopping), debug for the life of each connection and each
+    request, trace for the details of the traffic. The default level (info) stays quiet while
+    clients come and go.
+
+    A connection is served by one thread from start to end, `connection_span` tags every
+    record that thread logs meanwhile with the connection id and the peer (a worker serving
+    several connections re-enters the span of each one with `enter`). The fields of a
+    record (`debug!(message_type = "add_request", latency_us = 12; "Request served")`) and
+    the ones of the connection are written after the message as `key=value`:
+
+        [2024-01-01T12:00:00Z DEBUG embedded_recruitment_task::server] Request served message_type=add_request latency_us=12 conn=3 peer=127.0.0.1:51234
+*/
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    LevelFilter, Record,
+};
+use std::{
+    cell::RefCell,
+    fmt,
+    io::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static CONNECTION: RefCell<Option<Connection>> = const { RefCell::new(None) };
+}
+
+/* The connection the current thread is serving */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub id: u64,
+    pub peer: String,
+}
+
+/*
+    Installs the logger of the server binary. RUST_LOG can narrow the output down to some
+    modules (`RUST_LOG=embedded_recruitment_task::http=trace`), `level` is the ceiling and
+    can be changed later with `log::set_max_level` (a reload does that).
+*/
+pub fn init(level: LevelFilter) {
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Trace)
+        .parse_default_env()
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}{}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                record.args(),
+                Fields(record)
+            )
+        })
+        .init();
+    log::set_max_level(level);
+}
+
+/*
+    Tags the records logged by the current thread with a new connection id and `peer` until
+    the returned guard is dropped.
+*/
+pub fn connection_span(peer: &str) -> ConnectionSpan {
+    let connection = Connection {
+        id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+        peer: peer.to_string(),
+    };
+    let id = connection.id;
+    let outer = CONNECTION.with(|current| current.replace(Some(connection)));
+    ConnectionSpan { id, outer }
+}
+
+/*
+    Tags the records logged by the current thread with `connection` again until the returned
+    guard is dropped, for a worker serving several connections in turns.
+*/
+pub fn enter(connection: &Connection) -> ConnectionSpan {
+    let outer = CONNECTION.with(|current| current.replace(Some(connection.clone())));
+    ConnectionSpan { id: connection.id, outer }
+}
+
+/* The connection the current thread is serving, if any */
+pub fn current_connection() -> Option<Connection> {
+    CONNECTION.with(|current| current.borrow().clone())
+}
+
+/* Guard of `connection_span`, restores the previous connection (usually none) when dropped */
+pub struct ConnectionSpan {
+    id: u64,
+    outer: Option<Connection>,
+}
+
+impl ConnectionSpan {
+    /* The id the connection was given, unique for the life of the process */
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for ConnectionSpan {
+    fn drop(&mut self) {
+        let outer = self.outer.take();
+        CONNECTION.with(|current| *current.borrow_mut() = outer);
+    }
+}
+
+/* Writes the fields of a record followed by the ones of the current connection */
+struct Fields<'a, 'r>(&'a Record<'r>);
+
+impl fmt::Display for Fields<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = FieldWriter { f, result: Ok(()) };
+        /* the writer only fails when the formatter does */
+        let _ = self.0.key_values().visit(&mut writer);
+        writer.result?;
+        if let Some(connection) = current_connection() {
+            write!(f, " conn={} peer={}", connection.id, connection.peer)?;
+        }
+        Ok(())
+    }
+}
+
+struct FieldWriter<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    result: fmt::Result,
+}
+
+impl<'kvs> VisitSource<'kvs> for FieldWriter<'_, '_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.result = write!(self.f, " {}={}", key, value);
+        self.result.map_err(|_| kv::Error::msg("failed to write the field"))
+    }
+}
@@ -0,0 +1,313 @@
+/*
+    The server as a standalone process, see `server --help` for the options and
+    src/config.rs for the layout of the configuration file.
+
+    SIGINT (Ctrl-C) or SIGTERM stops the server gracefully, exactly like `Server::stop`: no new
+    clients are accepted and the requests in flight are completed. A second signal while the
+    server drains exits immediately.
+
+    SIGHUP reloads the configuration: the file given with --config is read again and the command
+    line options still override it. The connected clients are kept, the settings that can only
+    change with a restart (listeners, workers, recording, audit) are reported and keep their current value.
+
+    Exit codes:
+        0   the server stopped and every client was drained
+        1   the server could not start or failed while running
+        2   the drain timeout expired before every client was done
+        130 a second signal forced the exit
+*/
+use clap::Parser;
+use embedded_recruitment_task::{
+    config::{Config, ConfigError},
+    logging,
+    server::{Server, ServerError},
+};
+use log::{error, info, warn, LevelFilter};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    flag,
+};
+use std::{
+    io,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/* The process runs a single server, "Server-1" in the logs */
+const SERVER_ID: usize = 0;
+
+const EXIT_DRAINED: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_DRAIN_INCOMPLETE: i32 = 2;
+const EXIT_FORCED: i32 = 130;
+
+/// Echo/Add protobuf server.
+///
+/// Settings come from the defaults, then the --config file, then the command line options.
+#[derive(Debug, Parser)]
+#[command(name = "server", version)]
+struct Options {
+    /// TOML configuration file
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Validate the configuration, print the effective settings and exit
+    #[arg(long)]
+    check_config: bool,
+
+    /// Address the TCP listener binds to (use port 0 for any free port) [default: 127.0.0.1:8080]
+    #[arg(long)]
+    bind: Option<String>,
+
+    /// Also accept WebSocket clients on this address
+    #[arg(long)]
+    websocket: Option<String>,
+
+    /// Also serve the HTTP/JSON gateway on this address
+    #[arg(long)]
+    http: Option<String>,
+
+    /// Serve Prometheus metrics at http://<ADDR>/metrics (keep it on a local address)
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<String>,
+
+    /// Answer liveness/readiness probes at http://<ADDR>/livez and /readyz
+    #[arg(long, value_name = "ADDR")]
+    health: Option<String>,
+
+    /// Number of threads serving the clients, 0 for one thread per client [default: 0]
+    #[arg(long)]
+    workers: Option<usize>,
+
+    /// Disconnect new clients above this many connected clients
+    #[arg(long)]
+    max_connections: Option<usize>,
+
+    /// Close connections that send nothing for this long (milliseconds)
+    #[arg(long, value_name = "MS")]
+    read_timeout_ms: Option<u64>,
+
+    /// Close connections that do not read their response for this long (milliseconds)
+    #[arg(long, value_name = "MS")]
+    write_timeout_ms: Option<u64>,
+
+    /// Close connections whose request takes longer than this (milliseconds)
+    #[arg(long, value_name = "MS")]
+    request_timeout_ms: Option<u64>,
+
+    /// How long clients may take to finish their request on shutdown (milliseconds) [default: 30000]
+    #[arg(long, value_name = "MS")]
+    drain_timeout_ms: Option<u64>,
+
+    /// Largest request frame accepted, in bytes [default: 65536]
+    #[arg(long, value_name = "BYTES")]
+    max_frame_size: Option<usize>,
+
+    /// Record the traffic of the TCP clients to this file (JSON Lines, appended to)
+    #[arg(long, value_name = "PATH")]
+    record: Option<String>,
+
+    /// Write one line per request served to this audit log (JSON Lines, rotated by size)
+    #[arg(long, value_name = "PATH")]
+    audit: Option<String>,
+
+    /// Log level: off, error, warn, info, debug or trace [default: info]
+    #[arg(long)]
+    log_level: Option<LevelFilter>,
+}
+
+impl Options {
+    /* Builds the effective configuration: file values first, overridden by the options given */
+    fn config(&self) -> Result<Config, ConfigError> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        if let Some(bind) = &self.bind {
+            config.listeners.tcp = bind.clone();
+        }
+        if let Some(websocket) = &self.websocket {
+            config.listeners.websocket = Some(websocket.clone());
+        }
+        if let Some(http) = &self.http {
+            config.listeners.http = Some(http.clone());
+        }
+        if let Some(metrics) = &self.metrics {
+            config.listeners.metrics = Some(metrics.clone());
+        }
+        if let Some(health) = &self.health {
+            config.listeners.health = Some(health.clone());
+        }
+        if let Some(workers) = self.workers {
+            config.limits.workers = workers;
+        }
+        if let Some(max_connections) = self.max_connections {
+            config.limits.max_connections = Some(max_connections);
+        }
+        if let Some(max_frame_size) = self.max_frame_size {
+            config.limits.max_frame_size = max_frame_size;
+        }
+        if let Some(read_timeout_ms) = self.read_timeout_ms {
+            config.timeouts.read_ms = Some(read_timeout_ms);
+        }
+        if let Some(write_timeout_ms) = self.write_timeout_ms {
+            config.timeouts.write_ms = Some(write_timeout_ms);
+        }
+        if let Some(request_timeout_ms) = self.request_timeout_ms {
+            config.timeouts.request_ms = Some(request_timeout_ms);
+        }
+        if let Some(drain_timeout_ms) = self.drain_timeout_ms {
+            config.timeouts.drain_ms = drain_timeout_ms;
+        }
+        if let Some(record) = &self.record {
+            config.recording.path = Some(record.clone());
+        }
+        if let Some(audit) = &self.audit {
+            config.audit.path = Some(audit.clone());
+        }
+        if let Some(log_level) = self.log_level {
+            config.logging.level = log_level.as_str().to_lowercase();
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn main() {
+    let options = Options::parse();
+    let config = match options.config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("invalid configuration: {}", e);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    if options.check_config {
+        print!("{}", config.to_toml());
+        process::exit(EXIT_DRAINED);
+    }
+    /* the level is applied with `set_max_level` so a reload can change it */
+    logging::init(config.log_level());
+
+    process::exit(match serve(&options, config) {
+        Ok(()) => EXIT_DRAINED,
+        Err(e @ ServerError::Shutdown { .. }) => {
+            error!("{}", e);
+            EXIT_DRAIN_INCOMPLETE
+        }
+        Err(e) => {
+            error!("server error: {}", e);
+            EXIT_FAILURE
+        }
+    });
+}
+
+fn serve(options: &Options, mut config: Config) -> Result<(), ServerError> {
+    let mut server = Server::with_config(&config.listeners.tcp, config.server_config())?;
+    /* the addresses are not log lines: scripts read them on stdout, whatever the log level */
+    if let Some(addr) = &config.listeners.websocket {
+        println!("websocket listening on {}", server.enable_websocket(addr)?);
+    }
+    if let Some(addr) = &config.listeners.http {
+        println!("http listening on {}", server.enable_http(addr)?);
+    }
+    if let Some(addr) = &config.listeners.metrics {
+        println!("metrics listening on {}", server.enable_metrics(addr)?);
+    }
+    if let Some(addr) = &config.listeners.health {
+        println!("health listening on {}", server.enable_health(addr)?);
+    }
+    if let Some(path) = &config.recording.path {
+        server.enable_recording(path)?;
+    }
+    if let Some(audit) = config.audit_config() {
+        server.enable_audit(audit)?;
+    }
+    let server = Arc::new(server);
+    let shutdown_requested = install_signal_handlers()?;
+    let reload_requested = install_reload_handler()?;
+
+    println!("listening on {}", server.local_addr()?);
+
+    let runner = {
+        let server = Arc::clone(&server);
+        thread::spawn(move || server.run(SERVER_ID))
+    };
+
+    /* wait for a signal and turn it into the usual graceful stop */
+    while !runner.is_finished() {
+        if shutdown_requested.load(Ordering::SeqCst) {
+            info!("shutdown requested, draining clients (signal again to exit now)");
+            server.stop();
+            break;
+        }
+        if reload_requested.swap(false, Ordering::SeqCst) {
+            reload(&server, options, &mut config);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    runner
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("the server thread panicked").into()))
+}
+
+/*
+    Reads the configuration again and applies it to the running server. `running` is updated
+    with what was applied, an invalid configuration is reported and changes nothing.
+*/
+fn reload(server: &Server, options: &Options, running: &mut Config) {
+    let config = match options.config() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Reload failed, keeping the current configuration: {}", e);
+            return;
+        }
+    };
+
+    let report = config.reload(server, running);
+    if report.is_empty() {
+        info!("configuration reloaded, nothing changed");
+        return;
+    }
+    info!("configuration reloaded, applied: {:?}", report.applied);
+    if !report.restart_required.is_empty() {
+        warn!("These settings only change with a restart: {:?}", report.restart_required);
+    }
+}
+
+/*
+    Registers SIGINT and SIGTERM. The first one only raises the returned flag, a second one
+    arriving while the flag is raised terminates the process right away.
+*/
+fn install_signal_handlers() -> io::Result<Arc<AtomicBool>> {
+    let shutdown_requested = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        /* the order matters: the exit is only armed once the flag was raised by a first signal */
+        flag::register_conditional_shutdown(signal, EXIT_FORCED, Arc::clone(&shutdown_requested))?;
+        flag::register(signal, Arc::clone(&shutdown_requested))?;
+    }
+    Ok(shutdown_requested)
+}
+
+/* Registers SIGHUP, the returned flag is raised every time a reload is requested */
+#[cfg(unix)]
+fn install_reload_handler() -> io::Result<Arc<AtomicBool>> {
+    let reload_requested = Arc::new(AtomicBool::new(false));
+    flag::register(signal_hook::consts::SIGHUP, Arc::clone(&reload_requested))?;
+    Ok(reload_requested)
+}
+
+/* There is no SIGHUP outside of unix, a reload is never requested */
+#[cfg(not(unix))]
+fn install_reload_handler() -> io::Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
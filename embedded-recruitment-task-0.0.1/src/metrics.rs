@@ -0,0 +1,127 @@
+use crate::stats::{ServerStats, StatsSnapshot};
+use log::{debug, error, info, warn};
+use std::{fmt::Write, net::TcpListener, sync::Arc, time::Duration};
+use tiny_http::{Header, Method, Request, Response};
+
+/* How long we wait for a scrape before checking whether the server was stopped */
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/*
+    Serves `GET /metrics` on `listener`, the counters of `stats` in the Prometheus text format.
+    Like the health probes it runs until `run` is done, the drain can be watched to its end.
+*/
+pub(crate) fn serve(listener: TcpListener, id: usize, stats: Arc<ServerStats>) {
+    let http_server = match tiny_http::Server::from_listener(listener, None) {
+        Ok(http_server) => http_server,
+        Err(e) => {
+            error!("Server-{}: Failed to start the metrics endpoint: {}", id + 1, e);
+            return;
+        }
+    };
+
+    while stats.health().live {
+        match http_server.recv_timeout(POLL_INTERVAL) {
+            Ok(Some(request)) => handle_request(request, id, &stats),
+            Ok(None) => {}
+            Err(e) => error!("Server-{}: Error receiving metrics request: {}", id + 1, e),
+        }
+    }
+    info!("Server-{}: Metrics endpoint stopped.", id + 1);
+}
+
+fn handle_request(request: Request, id: usize, stats: &ServerStats) {
+    debug!("Server-{}: Metrics {} {}", id + 1, request.method(), request.url());
+
+    /* the query string is not part of the endpoint */
+    let path = request.url().split('?').next().unwrap_or_default();
+    let response = match (request.method(), path) {
+        (Method::Get, "/metrics") => Response::from_string(render(&stats.snapshot())).with_header(
+            Header::from_bytes("Content-Type", "text/plain; version=0.0.4; charset=utf-8").unwrap(),
+        ),
+        (_, "/metrics") => Response::from_string("only GET is allowed\n").with_status_code(405),
+        _ => Response::from_string("not found, the metrics are at /metrics\n").with_status_code(404),
+    };
+    if let Err(e) = request.respond(response) {
+        warn!("Server-{}: Failed to send the metrics: {}", id + 1, e);
+    }
+}
+
+/* Writes the counters in the Prometheus text exposition format */
+pub(crate) fn render(stats: &StatsSnapshot) -> String {
+    let mut out = String::new();
+    let single = |value: u64| [(String::new(), value)];
+    let labelled = |label: &str, counts: &std::collections::BTreeMap<String, u64>| -> Vec<(String, u64)> {
+        counts
+            .iter()
+            .map(|(name, count)| (format!("{{{}=\"{}\"}}", label, name), *count))
+            .collect()
+    };
+
+    let _ = writeln!(out, "# HELP server_uptime_seconds Time since the server was created.");
+    let _ = writeln!(out, "# TYPE server_uptime_seconds gauge");
+    let _ = writeln!(out, "server_uptime_seconds {}", stats.uptime.as_secs_f64());
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    metric(
+        "server_connections_accepted_total",
+        "counter",
+        "Connections accepted (TCP, WebSocket, one per HTTP request).",
+        &single(stats.accepted_connections),
+    );
+    metric(
+        "server_connections_active",
+        "gauge",
+        "Connections currently open (TCP, WebSocket, one per HTTP request).",
+        &single(stats.active_connections),
+    );
+    metric(
+        "server_connections_rejected_total",
+        "counter",
+        "Connections turned away because max_connections was reached.",
+        &single(stats.rejected_connections),
+    );
+    metric(
+        "server_requests_total",
+        "counter",
+        "Requests served, by message type.",
+        &labelled("type", &stats.requests),
+    );
+    metric(
+        "server_errors_total",
+        "counter",
+        "Error responses, timeouts and broken connections, by kind.",
+        &labelled("kind", &stats.errors),
+    );
+    metric(
+        "server_received_bytes_total",
+        "counter",
+        "Bytes read from the TCP and WebSocket clients.",
+        &single(stats.bytes_received),
+    );
+    metric(
+        "server_sent_bytes_total",
+        "counter",
+        "Bytes written to the TCP and WebSocket clients.",
+        &single(stats.bytes_sent),
+    );
+
+    let name = "server_request_duration_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {} Time from the first byte of a request to its response being ready.",
+        name
+    );
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (bound, count) in &stats.latency.buckets {
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound.as_secs_f64(), count);
+    }
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, stats.latency.count);
+    let _ = writeln!(out, "{}_sum {}", name, stats.latency.sum.as_secs_f64());
+    let _ = writeln!(out, "{}_count {}", name, stats.latency.count);
+    out
+}
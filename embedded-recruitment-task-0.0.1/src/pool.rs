@@ -0,0 +1,294 @@
+/*
+    A pool of client connections to one server, shared by any number of threads.
+
+        let pool = Pool::new("127.0.0.1:8080", PoolConfig::default())?;
+        let mut client = pool.get()?;
+        assert_eq!(client.add(1, 2)?, 3);
+        // dropping `client` puts the connection back in the pool
+
+    The pool opens `min_size` connections up front and up to `max_size` when they are all in
+    use, after that `get` waits up to `checkout_timeout` for one to come back. A connection that
+    stayed idle for `health_check_interval` is pinged before being handed out. Connections that
+    failed a ping or broke while checked out are closed and, below `min_size`, replaced in the
+    background.
+*/
+use crate::{
+    client::{Client, ClientConfig, ClientError},
+    message::{client_message, server_message, ClientMessage, ServerMessage},
+};
+use log::warn;
+use std::{
+    collections::VecDeque,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/* Settings of a Pool */
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /* connections kept open even when nobody uses them */
+    pub min_size: usize,
+    /* most connections open at the same time, idle and in use */
+    pub max_size: usize,
+    /* how long `get` waits for a connection when `max_size` of them are in use */
+    pub checkout_timeout: Duration,
+    /* an idle connection older than this is pinged before being handed out, None to never ping */
+    pub health_check_interval: Option<Duration>,
+    /* settings of every connection */
+    pub client: ClientConfig,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            min_size: 1,
+            max_size: 8,
+            checkout_timeout: Duration::from_secs(5),
+            health_check_interval: Some(Duration::from_secs(30)),
+            client: ClientConfig::default(),
+        }
+    }
+}
+
+/* How many connections the pool holds right now */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    pub idle: usize,
+    pub in_use: usize,
+}
+
+/* A pool of connections, cloning it gives another handle on the same pool */
+#[derive(Clone)]
+pub struct Pool {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    addrs: Vec<SocketAddr>,
+    config: PoolConfig,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+struct State {
+    idle: VecDeque<Idle>,
+    /* connections open or being opened, idle ones included */
+    open: usize,
+}
+
+struct Idle {
+    client: Client,
+    since: Instant,
+}
+
+impl Pool {
+    /* Resolves `addr` and opens the first `min_size` connections */
+    pub fn new(addr: impl ToSocketAddrs, config: PoolConfig) -> Result<Pool, ClientError> {
+        if config.max_size == 0 || config.min_size > config.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the pool needs 0 < max_size and min_size <= max_size",
+            )
+            .into());
+        }
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the address does not resolve").into());
+        }
+
+        let mut idle = VecDeque::new();
+        for _ in 0..config.min_size {
+            idle.push_back(Idle {
+                client: Client::with_config(addrs.as_slice(), config.client.clone())?,
+                since: Instant::now(),
+            });
+        }
+        let open = idle.len();
+        Ok(Pool {
+            shared: Arc::new(Shared {
+                addrs,
+                config,
+                state: Mutex::new(State { idle, open }),
+                returned: Condvar::new(),
+            }),
+        })
+    }
+
+    /*
+        Checks a connection out of the pool: an idle one if there is one, a new one below
+        `max_size`, otherwise the first one given back within `checkout_timeout`.
+    */
+    pub fn get(&self) -> Result<PooledClient, ClientError> {
+        let shared = &self.shared;
+        let deadline = Instant::now() + shared.config.checkout_timeout;
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            if let Some(idle) = state.idle.pop_front() {
+                drop(state);
+                match shared.check(idle) {
+                    Some(client) => return Ok(self.pooled(client)),
+                    None => {
+                        /* it failed the ping, its slot is free again */
+                        shared.closed();
+                        state = shared.state.lock().unwrap();
+                        continue;
+                    }
+                }
+            }
+
+            if state.open < shared.config.max_size {
+                state.open += 1;
+                drop(state);
+                return match Client::with_config(shared.addrs.as_slice(), shared.config.client.clone()) {
+                    Ok(client) => Ok(self.pooled(client)),
+                    Err(e) => {
+                        shared.closed();
+                        Err(e)
+                    }
+                };
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ClientError::CheckoutTimeout(shared.config.checkout_timeout));
+            }
+            state = shared.returned.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        let state = self.shared.state.lock().unwrap();
+        PoolStatus {
+            idle: state.idle.len(),
+            in_use: state.open - state.idle.len(),
+        }
+    }
+
+    fn pooled(&self, client: Client) -> PooledClient {
+        PooledClient {
+            client: Some(client),
+            shared: Arc::clone(&self.shared),
+            broken: false,
+        }
+    }
+}
+
+impl Shared {
+    /* Pings an idle connection if it waited long enough for that, None if it is broken */
+    fn check(&self, mut idle: Idle) -> Option<Client> {
+        let due = match self.config.health_check_interval {
+            Some(interval) => idle.since.elapsed() >= interval,
+            None => false,
+        };
+        if due && idle.client.ping().is_err() {
+            return None;
+        }
+        Some(idle.client)
+    }
+
+    fn give_back(&self, client: Client) {
+        let mut state = self.state.lock().unwrap();
+        state.idle.push_back(Idle {
+            client,
+            since: Instant::now(),
+        });
+        drop(state);
+        self.returned.notify_one();
+    }
+
+    /* A connection was closed or could not be opened, open a replacement below min_size */
+    fn closed(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        state.open -= 1;
+        let replace = state.open < self.config.min_size;
+        if replace {
+            state.open += 1;
+        }
+        drop(state);
+        self.returned.notify_one();
+
+        if replace {
+            let shared = Arc::clone(self);
+            thread::spawn(move || match Client::with_config(shared.addrs.as_slice(), shared.config.client.clone()) {
+                Ok(client) => shared.give_back(client),
+                Err(e) => {
+                    warn!("Failed to replace a pooled connection: {}", e);
+                    let mut state = shared.state.lock().unwrap();
+                    state.open -= 1;
+                    drop(state);
+                    shared.returned.notify_one();
+                }
+            });
+        }
+    }
+}
+
+/*
+    A connection checked out of a Pool, it goes back to the pool when dropped. A request
+    failing because of the connection marks it broken, it is then closed instead.
+*/
+pub struct PooledClient {
+    client: Option<Client>,
+    shared: Arc<Shared>,
+    broken: bool,
+}
+
+impl PooledClient {
+    pub fn echo(&mut self, content: &str) -> Result<String, ClientError> {
+        let result = self.client().echo(content);
+        self.check(result)
+    }
+
+    pub fn add(&mut self, a: i32, b: i32) -> Result<i32, ClientError> {
+        let result = self.client().add(a, b);
+        self.check(result)
+    }
+
+    pub fn ping(&mut self) -> Result<(), ClientError> {
+        let result = self.client().ping();
+        self.check(result)
+    }
+
+    pub fn call(&mut self, request: client_message::Message) -> Result<server_message::Message, ClientError> {
+        let result = self.client().call(request);
+        self.check(result)
+    }
+
+    pub fn send(&mut self, request: ClientMessage) -> Result<ServerMessage, ClientError> {
+        let result = self.client().send(request);
+        self.check(result)
+    }
+
+    /* Closes the connection instead of giving it back to the pool */
+    pub fn discard(mut self) {
+        self.broken = true;
+    }
+
+    fn client(&mut self) -> &mut Client {
+        self.client.as_mut().expect("the client is only taken on drop")
+    }
+
+    fn check<T>(&mut self, result: Result<T, ClientError>) -> Result<T, ClientError> {
+        if let Err(e) = &result {
+            if e.is_connection_error() {
+                self.broken = true;
+            }
+        }
+        result
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        let client = self.client.take().expect("dropped once");
+        if self.broken {
+            let _ = client.close();
+            self.shared.closed();
+        } else {
+            self.shared.give_back(client);
+        }
+    }
+}
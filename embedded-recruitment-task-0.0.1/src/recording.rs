@@ -0,0 +1,153 @@
+/*
+    Recording of the traffic of the TCP clients, to replay it later against another server.
+
+    A recording is a JSON Lines file, one Record per frame received or sent:
+
+        {"session":1,"peer":"127.0.0.1:50312","timestamp_us":1718000000000000,"direction":"request",
+         "frame":"0a040a026869","message":{"message":{"echo_message":{"content":"hi"}}}}
+
+    `frame` is the hex encoded payload of the frame as it was on the wire, `message` its decoded
+    form for humans (null when the frame could not be decoded). The responses of a session
+    follow the requests in the order they were answered.
+*/
+use crate::{
+    hex,
+    message::{ClientMessage, ServerMessage},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/* Which way a recorded frame went */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /* from the client to the server, a ClientMessage */
+    Request,
+    /* from the server to the client, a ServerMessage */
+    Response,
+}
+
+/* One line of a recording */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub session: u64,
+    pub peer: String,
+    /* microseconds since the UNIX epoch */
+    pub timestamp_us: u64,
+    pub direction: Direction,
+    pub frame: String,
+    pub message: serde_json::Value,
+}
+
+impl Record {
+    /* The payload of the frame, None if `frame` is not valid hex */
+    pub fn frame_bytes(&self) -> Option<Vec<u8>> {
+        hex::decode(&self.frame)
+    }
+}
+
+/* Appends the records of every session to one file, shared by all the connections */
+pub struct Recorder {
+    file: Mutex<LineWriter<File>>,
+    next_session: AtomicU64,
+    failed: AtomicBool,
+}
+
+impl Recorder {
+    /* Opens `path` for appending, creating it if needed */
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Recorder> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder {
+            file: Mutex::new(LineWriter::new(file)),
+            next_session: AtomicU64::new(1),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /* Starts recording a new session for the client at `peer` */
+    pub fn session(self: &Arc<Self>, peer: &str) -> Session {
+        Session {
+            recorder: Arc::clone(self),
+            id: self.next_session.fetch_add(1, Ordering::Relaxed),
+            peer: peer.to_string(),
+        }
+    }
+
+    fn write(&self, record: &Record) {
+        let line = serde_json::to_string(record).expect("records are serializable");
+        let result = writeln!(self.file.lock().unwrap(), "{}", line);
+        /* a recording that can't be written must not take the server down, say it once */
+        if let Err(e) = result {
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                warn!("Failed to write the traffic recording, records are being lost: {}", e);
+            }
+        }
+    }
+}
+
+/* The recording of one connection */
+pub struct Session {
+    recorder: Arc<Recorder>,
+    id: u64,
+    peer: String,
+}
+
+impl Session {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /* Records a frame received from the client, `message` is None if it could not be decoded */
+    pub fn request(&self, frame: &[u8], message: Option<&ClientMessage>) {
+        let message = message.map(|message| serde_json::to_value(message).expect("messages are serializable"));
+        self.record(Direction::Request, frame, message);
+    }
+
+    /* Records a response sent to the client */
+    pub fn response(&self, frame: &[u8], message: &ServerMessage) {
+        let message = serde_json::to_value(message).expect("messages are serializable");
+        self.record(Direction::Response, frame, Some(message));
+    }
+
+    fn record(&self, direction: Direction, frame: &[u8], message: Option<serde_json::Value>) {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or(0);
+        self.recorder.write(&Record {
+            session: self.id,
+            peer: self.peer.clone(),
+            timestamp_us,
+            direction,
+            frame: hex::encode(frame),
+            message: message.unwrap_or(serde_json::Value::Null),
+        });
+    }
+}
+
+/* Reads every record of a recording, in the order they were written */
+pub fn read_recording(path: impl AsRef<Path>) -> io::Result<Vec<Record>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, e))
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
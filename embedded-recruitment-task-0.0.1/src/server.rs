@@ -1,273 +1,1267 @@
-use crate::message;
-use log::{error, info, warn};
+use crate::{
+    audit::{AuditConfig, AuditLog, AuditSession},
+    framing::{self, FrameDecoder, FrameError},
+    health::{self, Health},
+    http, logging, message, metrics,
+    recording::{Recorder, Session},
+    stats::{ServerStats, StatsSnapshot, TimeoutKind},
+    transport::{Acceptor, Transport},
+    websocket,
+};
+use log::{debug, error, info, trace, warn};
 use prost::Message;
+use serde::{Deserialize, Serialize};
 use std::{
-    io::{self, ErrorKind, Read, Write},
-    net::{TcpListener, TcpStream},
+    fmt,
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender, TryRecvError},
+        Arc, RwLock,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use std::sync::Mutex;
-use once_cell::sync::Lazy;
 use crate::server::thread::JoinHandle;
 
-static CLIENT_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(())); 
+/* How long the accept loop waits for a connection before it looks whether the server was stopped */
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/* How long a read or a write waits before the handler gets a chance to check its timeouts */
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/*
+    Once the server is stopped, how long a client may go without sending or reading anything
+    before its request in flight is given up. Without it a peer stopping in the middle of a
+    frame would keep `run` from returning when no read or drain timeout is configured.
+*/
+pub(crate) const DRAIN_STALL_LIMIT: Duration = Duration::from_secs(1);
 
-pub struct Client {
-    stream: TcpStream,
+/* How long a worker waits for each of its clients when it serves several of them, see start_workers */
+const SHARED_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/* Why a connection was closed by the server, or why the server itself stopped with an error */
+#[derive(Debug)]
+pub enum ServerError {
+    /* binding, accepting, reading from or writing to a socket failed */
+    Io(io::Error),
+    /* the client sent bytes that can't be split into frames */
+    Frame(FrameError),
+    /*
+        the client sent a frame that is not a ClientMessage, it was answered with
+        INVALID_REQUEST and the connection closed: the peer does not speak the protocol
+    */
+    Decode(prost::DecodeError),
+    /* the client went over one of the timeouts of the ServerConfig */
+    Timeout { kind: TimeoutKind, limit: Duration },
+    /* the server stopped while some clients were still busy when the drain timeout expired */
+    Shutdown { drain_timeout: Duration },
 }
 
-impl Client {
-    pub fn new(stream: TcpStream) -> Self {
-        Client {
-            stream
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Io(e) => write!(f, "connection error: {}", e),
+            ServerError::Frame(e) => write!(f, "invalid frame: {}", e),
+            ServerError::Decode(e) => write!(f, "invalid ClientMessage: {}", e),
+            ServerError::Timeout { kind, limit } => write!(f, "{:?} timeout ({:?})", kind, limit),
+            ServerError::Shutdown { drain_timeout } => {
+                write!(f, "some clients were still busy after the drain timeout ({:?})", drain_timeout)
             }
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::Io(e) => Some(e),
+            ServerError::Frame(e) => Some(e),
+            ServerError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(e: io::Error) -> Self {
+        ServerError::Io(e)
+    }
+}
+
+impl From<FrameError> for ServerError {
+    fn from(e: FrameError) -> Self {
+        ServerError::Frame(e)
+    }
+}
+
+impl From<prost::DecodeError> for ServerError {
+    fn from(e: prost::DecodeError) -> Self {
+        ServerError::Decode(e)
+    }
+}
+
+/*
+    Settings applied to every connection accepted by the server.
+    A timeout set to None is disabled.
+*/
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /* how long a connection may stay without sending anything before it is closed */
+    pub read_timeout: Option<Duration>,
+    /* how long writing one response may take before the connection is closed */
+    pub write_timeout: Option<Duration>,
+    /* how long a whole request may take, from its first byte to having written the response */
+    pub request_timeout: Option<Duration>,
+    /* largest frame (encoded ClientMessage) a client may send */
+    pub max_frame_size: usize,
+    /* how long `run` waits for the clients to finish their current request once stopped */
+    pub drain_timeout: Option<Duration>,
+    /*
+        number of threads serving the clients, each one serves its clients in turns so more
+        clients than workers are all served (with a longer wait between two requests of a
+        client). 0 means one thread per client.
+    */
+    pub workers: usize,
+    /*
+        most clients (TCP, WebSocket, HTTP requests in flight) connected at the same time, the
+        ones above are disconnected right away (an HTTP request is answered with 503)
+    */
+    pub max_connections: Option<usize>,
+    /* which requests the server answers */
+    pub services: Services,
+}
+
+/* The services the server offers, a disabled one is answered with SERVICE_DISABLED */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Services {
+    pub echo: bool,
+    pub add: bool,
+}
+
+impl Default for Services {
+    fn default() -> Self {
+        Services { echo: true, add: true }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            read_timeout: None,
+            write_timeout: None,
+            request_timeout: None,
+            max_frame_size: framing::DEFAULT_MAX_FRAME_SIZE,
+            drain_timeout: None,
+            workers: 0,
+            max_connections: None,
+            services: Services::default(),
+        }
+    }
+}
+
+/* What `Server::reload` did with the settings that changed */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    /* settings now in effect, the connected clients included */
+    pub applied: Vec<&'static str>,
+    /* settings that only change with a restart, they keep their current value until then */
+    pub restart_required: Vec<&'static str>,
+}
+
+impl ReloadReport {
+    /* True when the new configuration was the same as the current one */
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
+}
+
+/*
+    The ServerConfig shared by the server and every connection, `Server::reload` swaps it while
+    they run. The generation goes up with every reload so the connections know when to pick up
+    the new settings without locking on every poll.
+*/
+pub(crate) struct LiveConfig {
+    config: RwLock<ServerConfig>,
+    generation: AtomicU64,
+}
+
+impl LiveConfig {
+    fn new(config: ServerConfig) -> Self {
+        LiveConfig {
+            config: RwLock::new(config),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self) -> ServerConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn services(&self) -> Services {
+        self.config.read().unwrap().services.clone()
+    }
+
+    fn replace(&self, config: ServerConfig) {
+        *self.config.write().unwrap() = config;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/*
+    Handler of one connected client.
+    It keeps the state of the connection between two calls to `handle`: the bytes of a frame
+    that did not completely arrive yet and the bytes of the responses that could not be
+    written yet, so that nothing is lost when the socket has no data or no room.
+    The stream is a TcpStream unless the client comes through another Transport (an
+    in-memory pipe in the tests).
+*/
+pub struct Client<S: Transport = TcpStream> {
+    stream: S,
+    peer: String,
+    config: ServerConfig,
+    stats: Arc<ServerStats>,
+    inbound: FrameDecoder, // bytes received but not decoded yet
+    outbound: Vec<u8>, // encoded responses waiting to be written
+    written: usize, // how much of `outbound` was already written
+    last_activity: Instant, // last time the client sent something
+    request_started: Option<Instant>, // first byte of the request being served
+    write_stalled_since: Option<Instant>, // since when the client does not read its responses
+    last_progress: Instant, // last time bytes were received from or sent to the client
+    recording: Option<Session>, // where the frames are recorded, if the server records the traffic
+    audit: Option<AuditSession>, // where the requests are audited, if the server keeps an audit log
+    closed: bool,
+}
+
+impl<S: Transport> Client<S> {
+    pub fn new(stream: S) -> Self {
+        Client::with_config(stream, ServerConfig::default(), Arc::new(ServerStats::default()))
+    }
+
+    /* Creates a client handler applying the limits of `config` and counting timeouts in `stats` */
+    pub fn with_config(stream: S, config: ServerConfig, stats: Arc<ServerStats>) -> Self {
+        let peer = stream.peer();
+
+        /* never park forever in `read` or `write`, WouldBlock means "try later" */
+        if let Err(e) = stream.set_poll_interval(POLL_INTERVAL) {
+            warn!("Failed to configure the socket of {}: {}", peer, e);
+        }
+
+        Client {
+            stream,
+            peer,
+            inbound: FrameDecoder::new(config.max_frame_size),
+            config,
+            stats,
+            outbound: Vec::new(),
+            written: 0,
+            last_activity: Instant::now(),
+            request_started: None,
+            write_stalled_since: None,
+            last_progress: Instant::now(),
+            recording: None,
+            audit: None,
+            closed: false,
+        }
+    }
+
+    /*
+        Applies new settings to the connection, the request in flight is kept. A smaller
+        max_frame_size only applies to the frames that are not buffered yet.
+    */
+    pub fn set_config(&mut self, config: ServerConfig) {
+        self.inbound.set_max_frame_size(config.max_frame_size);
+        self.config = config;
+    }
+
+    /* Records every frame received and sent from now on in `session` */
+    pub fn record_to(&mut self, session: Session) {
+        self.recording = Some(session);
+    }
+
+    /* Writes one audit entry per request served from now on in `session` */
+    pub fn audit_to(&mut self, session: AuditSession) {
+        self.audit = Some(session);
+    }
+
+    /* True once the client disconnected or the connection was closed by us */
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /* True while a request is in flight: part of it was received or part of its answer is not sent yet */
+    pub fn is_busy(&self) -> bool {
+        self.inbound.has_partial_frame() || self.written < self.outbound.len()
+    }
+
+    /* How long a `handle` call waits for the client at most when it has nothing to say */
+    pub fn set_poll_interval(&self, interval: Duration) {
+        if let Err(e) = self.stream.set_poll_interval(interval) {
+            warn!("Failed to configure the socket of {}: {}", self.peer, e);
+        }
+    }
+
+    /* How long ago bytes last moved on the connection, in either direction */
+    pub fn idle_for(&self) -> Duration {
+        self.last_progress.elapsed()
     }
 
-    pub fn handle(&mut self, id: usize) -> io::Result<()> {
-        println!("server-{}: Handling.....", id + 1);
+    /*
+        Makes as much progress as possible on the connection: finishes writing pending responses,
+        reads what the client sent and answers every complete frame. Returns Ok(()) when there is
+        nothing more to do for now and an error when the connection has to be closed.
+    */
+    pub fn handle(&mut self, id: usize) -> Result<(), ServerError> {
+        /* the client has to read its previous answers before we read more of its requests */
+        if !self.flush(id)? {
+            return Ok(());
+        }
+
         let mut buffer = [0; 512];
-        println!("server-{}: Buffer initialized with size: {}", id + 1, buffer.len());
-        
-        /*take a lock on this mutex so that only one thread read and write on the tcp */
-        let lock = CLIENT_MUTEX.lock().unwrap();
         let bytes_read = {
             match self.stream.read(&mut buffer) {
                 Ok(0) => {
-                    println!("server-{}: Server disconnected (read returned 0 bytes).", id + 1);
+                    if self.inbound.has_partial_frame() {
+                        warn!(
+                            "Server-{}: Client {} disconnected in the middle of a frame ({} bytes dropped).",
+                            id + 1,
+                            self.peer,
+                            self.inbound.buffered()
+                        );
+                    }
+                    debug!("Server-{}: Client {} disconnected.", id + 1, self.peer);
+                    self.closed = true;
                     return Ok(());
                 }
-                Ok(bytes) => {
-                    println!("server-{}: Read operation completed ,Bytes read: {}", id + 1, bytes);
-                    bytes
-                }
-                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    println!("No data available yet, retrying...");
-                    thread::sleep(Duration::from_millis(100));
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    /* nothing arrived during the poll interval, give up on the client if it stalled for too long */
+                    if let Some(read_timeout) = self.config.read_timeout {
+                        if self.last_activity.elapsed() >= read_timeout {
+                            return Err(self.timed_out(id, TimeoutKind::Read, read_timeout));
+                        }
+                    }
+                    self.check_request_timeout(id)?;
                     return Ok(());
                 }
-                Err(e) => {
-                    println!("server-{}: Read error: {}", id + 1, e);
-                    return Err(io::Error::new(
-                        io::ErrorKind::ConnectionReset,
-                        format!("Failed to read from client: {}", e),
-                    ));
-                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(()),
+                Err(e) => return Err(e.into()),
             }
-        };    
-        // Attempt to decode the client message
-        if let Ok(client_message) = message::ClientMessage::decode(&buffer[..bytes_read]) {
-            match client_message.message {
-                Some(message::client_message::Message::AddRequest(add_request)) => {
-                    println!("server-{}: Received AddRequest: a = {}, b = {}", id + 1, add_request.a, add_request.b);
-                    
-                    // Handle AddRequest
-                    let result = add_request.a + add_request.b;
-                    let response = message::server_message::Message::AddResponse(message::AddResponse { result });
-    
-                    // Encode the response into the payload
-                    let mut payload = Vec::new();
-                    let server_message = message::ServerMessage {
-                        message: Some(response),
-                    };
+        };
+        self.stats.record_received(bytes_read);
+        let now = Instant::now();
+        self.last_activity = now;
+        self.last_progress = now;
+        if !self.inbound.has_partial_frame() {
+            self.request_started = Some(now);
+        }
+        self.inbound.extend(&buffer[..bytes_read]);
 
-                    // Handle encoding error
-                    if let Err(e) = server_message.encode(&mut payload) {
-                        println!("server-{}: Failed to encode AddResponse message: {}", id + 1, e);
-                        // Optionally, send an error message back to the client
-                        return Err(io::Error::new(io::ErrorKind::Other, "Failed to encode AddResponse message"));
+        /* answer every frame that is complete, a partial one stays buffered for the next call */
+        let mut received = self.request_started.unwrap_or(now);
+        let mut served = false;
+        loop {
+            match self.inbound.next_frame() {
+                Ok(Some(frame)) => {
+                    // Attempt to decode the client message, a request we can't decode is answered with an error
+                    let client_message = message::ClientMessage::decode(frame.as_slice());
+                    if let Some(recording) = &self.recording {
+                        recording.request(&frame, client_message.as_ref().ok());
                     }
-    
-                    // Write the encoded payload to the stream
-                    self.stream.write_all(&payload)?;
-                    self.stream.flush()?;
-                    println!("server-{}: AddResponse sent with result: {}", id + 1, result);
-                }
-                Some(message::client_message::Message::EchoMessage(msg)) => {
-                    println!("Received EchoMessage: '{}'", msg.content);
-    
-                    // Encode the EchoMessage into a ServerMessage
-                    let server_message = message::ServerMessage {
-                        message: Some(message::server_message::Message::EchoMessage(msg)),
+                    let message_type = client_message.as_ref().map_or("invalid", |m| m.message_type());
+                    let (server_message, decode_error) = match client_message {
+                        Ok(client_message) => {
+                            (dispatch(client_message, id, &self.config.services, &self.stats), None)
+                        }
+                        Err(e) => (
+                            error_response(message::ErrorCode::InvalidRequest, format!("invalid ClientMessage: {}", e)),
+                            Some(e),
+                        ),
                     };
-    
-                    let mut payload = Vec::new();
-                    // Handle the error when encoding the message
-                    if let Err(e) = server_message.encode(&mut payload) {
-                        println!("server-{}: Failed to encode EchoMessage: {}", id + 1, e);
-                        return Err(io::Error::new(io::ErrorKind::Other, format!("Encoding error: {}", e)));
+                    if let Some(recording) = &self.recording {
+                        recording.response(&server_message.encode_to_vec(), &server_message);
+                    }
+                    let latency = received.elapsed();
+                    self.stats.record_request(message_type, server_message.outcome(), latency);
+                    if let Some(audit) = &self.audit {
+                        audit.request(message_type, server_message.outcome(), latency, frame.len());
+                    }
+                    debug!(
+                        message_type = message_type,
+                        outcome = server_message.outcome(),
+                        latency_us = latency.as_micros() as u64,
+                        request_bytes = frame.len();
+                        "Server-{}: Request served.",
+                        id + 1
+                    );
+                    /* the next frames of this read arrived with it */
+                    received = now;
+                    served = true;
+                    self.outbound.extend(framing::encode_frame(&server_message));
+                    if let Some(e) = decode_error {
+                        /* the peer doesn't speak the protocol, whatever follows can't be trusted either */
+                        warn!("Server-{}: Closing client {}: invalid ClientMessage: {}", id + 1, self.peer, e);
+                        let _ = self.flush(id);
+                        let _ = self.stream.shutdown();
+                        self.closed = true;
+                        return Err(e.into());
                     }
-    
-                    // Write the encoded payload to the stream
-                    println!("Server-{}: Sending EchoMessage: '{}'", id + 1, payload[0]);
-                    self.stream.write_all(&payload)?;
-                    self.stream.flush()?;
-                    println!("Server-{}: Echoed back message successfully.", id + 1);
                 }
-                None => {
-                    println!("Server-{}: Received an empty or invalid message.",id + 1);
+                Ok(None) => break,
+                Err(e) => {
+                    /* we can't find the next frame boundary anymore, tell the client why and close */
+                    warn!("Server-{}: Closing client {}: {}", id + 1, self.peer, e);
+                    let code = match e {
+                        FrameError::TooLarge { .. } => message::ErrorCode::FrameTooLarge,
+                        FrameError::InvalidLength => message::ErrorCode::InvalidRequest,
+                    };
+                    let response = error_response(code, e.to_string());
+                    self.stats.record_error(response.outcome());
+                    self.outbound.extend(framing::encode_frame(&response));
+                    let _ = self.flush(id);
+                    let _ = self.stream.shutdown();
+                    self.closed = true;
+                    return Err(e.into());
                 }
             }
-        } else {
-            println!("Failed to decode in general");
         }
-    
-        drop(lock);
+        if self.inbound.has_partial_frame() {
+            if served {
+                /* the leftover bytes are the start of the next request */
+                self.request_started = Some(now);
+            } else {
+                /* still the same request, a peer sending it a byte at a time must not get around the limit */
+                self.check_request_timeout(id)?;
+            }
+        }
+
+        self.flush(id)?;
         Ok(())
     }
+
+    /*
+        Writes as much of the queued responses as the socket accepts.
+        Returns true when everything was written, false when some bytes are still queued.
+    */
+    fn flush(&mut self, id: usize) -> Result<bool, ServerError> {
+        while self.written < self.outbound.len() {
+            match self.stream.write(&self.outbound[self.written..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write the response").into());
+                }
+                Ok(bytes) => {
+                    self.stats.record_sent(bytes);
+                    self.written += bytes;
+                    self.write_stalled_since = None;
+                    self.last_progress = Instant::now();
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if self.written == self.outbound.len() {
+            if !self.outbound.is_empty() {
+                trace!(response_bytes = self.outbound.len(); "Server-{}: Response sent.", id + 1);
+                self.outbound.clear();
+                self.written = 0;
+            }
+            if !self.inbound.has_partial_frame() {
+                self.request_started = None;
+            }
+            return Ok(true);
+        }
+
+        /* the client does not read its responses fast enough */
+        let stalled_since = *self.write_stalled_since.get_or_insert_with(Instant::now);
+        if let Some(write_timeout) = self.config.write_timeout {
+            if stalled_since.elapsed() >= write_timeout {
+                return Err(self.timed_out(id, TimeoutKind::Write, write_timeout));
+            }
+        }
+        self.check_request_timeout(id)?;
+        Ok(false)
+    }
+
+    /* Fails when the request being served (read or written) is taking longer than allowed */
+    fn check_request_timeout(&mut self, id: usize) -> Result<(), ServerError> {
+        if let (Some(request_timeout), Some(request_started)) = (self.config.request_timeout, self.request_started) {
+            if request_started.elapsed() >= request_timeout {
+                return Err(self.timed_out(id, TimeoutKind::Request, request_timeout));
+            }
+        }
+        Ok(())
+    }
+
+    /* Counts and logs a timeout, then closes the connection so the peer sees a clean end of stream */
+    fn timed_out(&mut self, id: usize, kind: TimeoutKind, limit: Duration) -> ServerError {
+        self.stats.record_timeout(kind);
+        warn!(
+            "Server-{}: {:?} timeout ({:?}) for client {}, closing the connection.",
+            id + 1,
+            kind,
+            limit,
+            self.peer
+        );
+        let _ = self.stream.shutdown();
+        self.closed = true;
+        ServerError::Timeout { kind, limit }
+    }
 }
 
-// Define the static vector
-static IS_RUNNING: OnceLock<Arc<[AtomicBool; 5]>> = OnceLock::new();
+/*
+    Handles one decoded client message and builds the response for it. This is shared by
+    every transport (TCP, WebSocket, HTTP) so a request gets the same answer whatever way
+    it came in. Requests that can't be served are answered with an ErrorResponse. A
+    StatsRequest and a HealthCheck are answered from `stats`.
 
-pub struct Server {
-    listener: TcpListener,
-    client_threads: Arc<Mutex<Vec<JoinHandle<()>>>>, // Track client threads
+    An AddRequest whose sum does not fit in an int32 is answered with an OVERFLOW
+    ErrorResponse, a ClientMessage without a message with INVALID_REQUEST.
+*/
+pub fn dispatch(
+    client_message: message::ClientMessage,
+    id: usize,
+    services: &Services,
+    stats: &ServerStats,
+) -> message::ServerMessage {
+    /* nobody waits for the answer anymore (the request waited for a worker too long), skip the work */
+    if deadline_passed(client_message.deadline_unix_ms) {
+        return error_response(
+            message::ErrorCode::DeadlineExceeded,
+            "the deadline of the request passed before it was served".to_string(),
+        );
+    }
+    match client_message.message {
+        Some(message::client_message::Message::AddRequest(_)) if !services.add => {
+            error_response(message::ErrorCode::ServiceDisabled, "the add service is disabled".to_string())
+        }
+        Some(message::client_message::Message::EchoMessage(_)) if !services.echo => {
+            error_response(message::ErrorCode::ServiceDisabled, "the echo service is disabled".to_string())
+        }
+        Some(message::client_message::Message::AddRequest(add_request)) => {
+            trace!("Server-{}: AddRequest a = {}, b = {}", id + 1, add_request.a, add_request.b);
+
+            // Handle AddRequest, an overflow is reported instead of wrapping or panicking
+            match add_request.a.checked_add(add_request.b) {
+                Some(result) => message::ServerMessage {
+                    message: Some(message::server_message::Message::AddResponse(message::AddResponse { result })),
+                },
+                None => error_response(
+                    message::ErrorCode::Overflow,
+                    format!("{} + {} does not fit in an int32", add_request.a, add_request.b),
+                ),
+            }
+        }
+        Some(message::client_message::Message::EchoMessage(msg)) => {
+            trace!("Server-{}: EchoMessage '{}'", id + 1, msg.content);
+
+            // Echo the same message back inside a ServerMessage
+            message::ServerMessage {
+                message: Some(message::server_message::Message::EchoMessage(msg)),
+            }
+        }
+        Some(message::client_message::Message::StatsRequest(_)) => {
+            trace!("Server-{}: StatsRequest", id + 1);
+            message::ServerMessage {
+                message: Some(message::server_message::Message::StatsResponse(stats_response(&stats.snapshot()))),
+            }
+        }
+        Some(message::client_message::Message::HealthCheck(_)) => {
+            trace!("Server-{}: HealthCheck", id + 1);
+            message::ServerMessage {
+                message: Some(message::server_message::Message::HealthResponse(health_response(stats.health()))),
+            }
+        }
+        None => {
+            debug!("Server-{}: Received a request without a message.", id + 1);
+            error_response(message::ErrorCode::InvalidRequest, "the request carries no message".to_string())
+        }
+    }
+}
+
+/* The answer to a StatsRequest */
+fn stats_response(stats: &StatsSnapshot) -> message::StatsResponse {
+    message::StatsResponse {
+        uptime_ms: stats.uptime.as_millis() as u64,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        active_connections: stats.active_connections,
+        requests: stats.requests.clone(),
+        errors: stats.errors.clone(),
+    }
+}
+
+/* The answer to a HealthCheck */
+fn health_response(health: Health) -> message::HealthResponse {
+    message::HealthResponse {
+        live: health.live,
+        ready: health.ready,
+        listening: health.listening,
+        draining: health.draining,
+        saturated: health.saturated,
+        active_connections: health.active_connections,
     }
+}
+
+/* Builds a ServerMessage carrying an ErrorResponse */
+pub fn error_response(code: message::ErrorCode, description: String) -> message::ServerMessage {
+    message::ServerMessage {
+        message: Some(message::server_message::Message::ErrorResponse(message::ErrorResponse {
+            code: code as i32,
+            message: description,
+        })),
+    }
+}
+
+/* Serves the connections of `A`, a TCP listener unless built `with_acceptor` */
+pub struct Server<A = TcpListener> {
+    listener: A, // Where the protocol connections come from
+    websocket_listener: Option<TcpListener>, // Optional WebSocket listener sharing the same handlers
+    http_listener: Option<TcpListener>, // Optional HTTP/JSON gateway sharing the same handlers
+    metrics_listener: Option<TcpListener>, // Optional Prometheus endpoint exposing `stats`
+    health_listener: Option<TcpListener>, // Optional liveness/readiness probe endpoint
+    client_threads: Arc<Mutex<Vec<JoinHandle<()>>>>, // Track client threads
+    config: Arc<LiveConfig>, // Settings applied to every accepted connection, replaced by `reload`
+    stats: Arc<ServerStats>, // Counters shared with the client threads
+    recorder: Option<Arc<Recorder>>, // Where the TCP traffic is recorded, if enabled
+    audit: Option<Arc<AuditLog>>, // Where the requests are audited, if enabled
+    running: Mutex<Arc<AtomicBool>>, // Cleared by `stop`, every thread of the current run watches it
+}
 
 impl Server {
     // Creates a new server instance
-    pub fn new(addr: &str,_id:i32) -> io::Result<Self> {
-        /* 
-            Initialize a static shared vector of 5 variable as we have 5 tests 
-            and each test will have its own is_runing variable 
-        */
-        IS_RUNNING.get_or_init(|| {
-            Arc::new([
-                AtomicBool::new(false),
-                AtomicBool::new(false),
-                AtomicBool::new(false),
-                AtomicBool::new(false),
-                AtomicBool::new(false),
-            ])
-        });
-        // let listener = TcpListener::bind(addr)?;
-        println!("------------------------------------------------------");
+    pub fn new(addr: &str) -> io::Result<Self> {
+        Server::with_config(addr, ServerConfig::default())
+    }
+
+    // Creates a new server instance applying `config` to every accepted connection
+    pub fn with_config(addr: &str, config: ServerConfig) -> io::Result<Self> {
         // Attempt to bind the listener
-        let listener = match TcpListener::bind(addr) {
-            Ok(listener) => {
-                println!("The Server is Successfully bound to address: {}", addr);
-                listener
-            }
-            Err(e) => {
-                // Log different error cases
-                match e.kind() {
-                    ErrorKind::AddrInUse => {
-                        println!("Error: The address {} is already in use.", addr);
-                    }
-                    ErrorKind::PermissionDenied => {
-                        println!("Error: Permission denied to bind to address: {}", addr);
-                    }
-                    _ => {
-                        println!("Error binding to address {}: {}", addr, e);
-                    }
-                }
-                // Return the error if binding fails
-                return Err(e); 
-            }
-        };
-        /* print the address that the server is listening to */
-        println!("The Server is initialized and listening on {}", addr);
-        println!("------------------------------------------------------");
-        Ok(Server { 
-            listener ,
+        let listener = bind(addr)?;
+        Ok(Server::with_acceptor(listener, config))
+    }
+
+    /* Returns the address the TCP listener is bound to (useful when binding to port 0) */
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl<A: Acceptor> Server<A> {
+    /*
+        Creates a server serving the connections of `listener` instead of a TCP listener, e.g.
+        a transport::MemoryListener so that tests don't need any port.
+    */
+    pub fn with_acceptor(listener: A, config: ServerConfig) -> Self {
+        Server {
+            listener,
+            websocket_listener: None,
+            http_listener: None,
+            metrics_listener: None,
+            health_listener: None,
             client_threads: Arc::new(Mutex::new(Vec::new())), // Initialize empty thread list
-        })
+            config: Arc::new(LiveConfig::new(config)),
+            stats: Arc::new(ServerStats::default()),
+            recorder: None,
+            audit: None,
+            /* armed from the start, so that a `stop` coming before `run` is not lost */
+            running: Mutex::new(Arc::new(AtomicBool::new(true))),
+        }
+    }
+
+    /* Returns the counters of the server (connections, requests, errors, latency, ...) */
+    pub fn stats(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /*
+        Returns whether the server is live (not stopped yet) and ready to take more clients:
+        listening, not draining and not saturated (see the health module).
+    */
+    pub fn health(&self) -> Health {
+        self.stats.health()
+    }
+
+    /* Returns the settings currently applied */
+    pub fn config(&self) -> ServerConfig {
+        self.config.get()
+    }
+
+    /*
+        Replaces the settings while the server runs, the connected clients stay connected and
+        use the new values from their next poll on. The number of workers is fixed once `run`
+        started, a new value is reported in `restart_required` and the current one is kept.
+    */
+    pub fn reload(&self, mut config: ServerConfig) -> ReloadReport {
+        let current = self.config.get();
+        let mut report = ReloadReport::default();
+
+        if config.workers != current.workers {
+            report.restart_required.push("workers");
+            config.workers = current.workers;
+        }
+        for (name, changed) in [
+            ("read_timeout", config.read_timeout != current.read_timeout),
+            ("write_timeout", config.write_timeout != current.write_timeout),
+            ("request_timeout", config.request_timeout != current.request_timeout),
+            ("max_frame_size", config.max_frame_size != current.max_frame_size),
+            ("drain_timeout", config.drain_timeout != current.drain_timeout),
+            ("max_connections", config.max_connections != current.max_connections),
+            ("services", config.services != current.services),
+        ] {
+            if changed {
+                report.applied.push(name);
+            }
+        }
+
+        if !report.applied.is_empty() {
+            self.stats.set_capacity(health::capacity(&config));
+            self.config.replace(config);
+        }
+        report
+    }
+
+    /*
+        Binds an extra WebSocket listener on `addr`. Every binary WebSocket message carries
+        one encoded ClientMessage and is answered with one binary ServerMessage.
+        Must be called before `run`.
+    */
+    pub fn enable_websocket(&mut self, addr: &str) -> io::Result<SocketAddr> {
+        let listener = bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("The Server is accepting WebSocket clients on {}", local_addr);
+        self.websocket_listener = Some(listener);
+        Ok(local_addr)
     }
 
-    /* Runs the server, listening for incoming connections and handling them */
-    pub fn run(&self, id: usize) -> io::Result<()> {
-        /* first get a reference to the static initialized vector IS_RUNING */
-        let is_running = IS_RUNNING.get().expect("Static vector not initialized");
-        
+    /* Returns the address of the WebSocket listener if it was enabled */
+    pub fn websocket_addr(&self) -> Option<SocketAddr> {
+        self.websocket_listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+    }
+
+    /*
+        Binds an extra HTTP listener on `addr` mapping `POST /echo` and `POST /add` JSON bodies
+        onto EchoMessage and AddRequest. Must be called before `run`.
+    */
+    pub fn enable_http(&mut self, addr: &str) -> io::Result<SocketAddr> {
+        let listener = bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("The Server is accepting HTTP requests on {}", local_addr);
+        self.http_listener = Some(listener);
+        Ok(local_addr)
+    }
+
+    /*
+        Records every frame exchanged with the TCP clients in `path` (appended to, see the
+        recording module for the format). Must be called before `run`.
+    */
+    pub fn enable_recording(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.recorder = Some(Arc::new(Recorder::create(&path)?));
+        info!("The Server is recording the traffic to {}", path.as_ref().display());
+        Ok(())
+    }
+
+    /*
+        Writes one line per request served (TCP, WebSocket or HTTP) to the audit log described
+        by `config`, see the audit module for the format and the rotation. Must be called
+        before `run`.
+    */
+    pub fn enable_audit(&mut self, config: AuditConfig) -> io::Result<()> {
+        let path = config.path.clone();
+        self.audit = Some(Arc::new(AuditLog::start(config)?));
+        info!("The Server is auditing the requests to {}", path.display());
+        Ok(())
+    }
+
+    /* Returns the address of the HTTP listener if it was enabled */
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http_listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+    }
+
+    /*
+        Binds an HTTP listener on `addr` answering `GET /metrics` with the counters of the
+        server in the Prometheus text format. Meant for a local address, there is no
+        authentication. Must be called before `run`.
+    */
+    pub fn enable_metrics(&mut self, addr: &str) -> io::Result<SocketAddr> {
+        let listener = bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("The Server is exposing its metrics on http://{}/metrics", local_addr);
+        self.metrics_listener = Some(listener);
+        Ok(local_addr)
+    }
+
+    /* Returns the address of the metrics listener if it was enabled */
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+    }
+
+    /*
+        Binds an HTTP listener on `addr` answering the `GET /livez` and `GET /readyz` probes
+        (see the health module). It keeps answering until `run` returns, draining included.
+        Must be called before `run`.
+    */
+    pub fn enable_health(&mut self, addr: &str) -> io::Result<SocketAddr> {
+        let listener = bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("The Server is answering health probes on http://{}/readyz", local_addr);
+        self.health_listener = Some(listener);
+        Ok(local_addr)
+    }
+
+    /* Returns the address of the health probe listener if it was enabled */
+    pub fn health_addr(&self) -> Option<SocketAddr> {
+        self.health_listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+    }
+
+    /*
+        Runs the server, listening for incoming connections and handling them until `stop` is
+        called. `id` only names the server in the logs ("Server-1" for 0). Once `run` returned
+        the server can be run again.
+    */
+    pub fn run(&self, id: usize) -> Result<(), ServerError> {
         /*
-            set the server to run (only the server crossponding to the passed index) 
-            by setting the value to true 
+            the flag of this run was armed when the previous one ended (or by `with_config`),
+            if it is already cleared then `stop` was called before we started and there is
+            nothing to run
         */
-        is_running[id].store(true, Ordering::SeqCst); // Set the server as running
-        println!("Server-{} is running on {}", id + 1, self.listener.local_addr()?);
+        let running = Arc::clone(&self.running.lock().unwrap());
+        self.stats.start_serving();
+        info!("Server-{} is running on {}", id + 1, self.listener.describe());
+
+        /* start the WebSocket acceptor next to the TCP one if it was enabled */
+        if let Some(websocket_listener) = &self.websocket_listener {
+            let websocket_listener = websocket_listener.try_clone()?;
+            websocket_listener.set_nonblocking(true)?;
+            let config = Arc::clone(&self.config);
+            let stats = Arc::clone(&self.stats);
+            let audit = self.audit.clone();
+            let running = Arc::clone(&running);
+            let handle =
+                thread::spawn(move || websocket::serve(websocket_listener, id, &running, config, stats, audit));
+            self.client_threads.lock().unwrap().push(handle);
+        }
+
+        /* same for the HTTP gateway */
+        if let Some(http_listener) = &self.http_listener {
+            let http_listener = http_listener.try_clone()?;
+            let config = Arc::clone(&self.config);
+            let stats = Arc::clone(&self.stats);
+            let audit = self.audit.clone();
+            let running = Arc::clone(&running);
+            let handle = thread::spawn(move || http::serve(http_listener, id, &running, config, stats, audit));
+            self.client_threads.lock().unwrap().push(handle);
+        }
+
+        /* the probes and the metrics outlive the client threads, they are joined after them */
+        let mut endpoint_threads = Vec::new();
+        if let Some(metrics_listener) = &self.metrics_listener {
+            let metrics_listener = metrics_listener.try_clone()?;
+            let stats = Arc::clone(&self.stats);
+            endpoint_threads.push(thread::spawn(move || metrics::serve(metrics_listener, id, stats)));
+        }
+        if let Some(health_listener) = &self.health_listener {
+            let health_listener = health_listener.try_clone()?;
+            let stats = Arc::clone(&self.stats);
+            endpoint_threads.push(thread::spawn(move || health::serve(health_listener, id, stats)));
+        }
+
+        /* with a fixed number of workers the accepted clients wait in a queue for a free worker */
+        let worker_queue = if self.config.get().workers > 0 {
+            Some(self.start_workers(id, &running))
+        } else {
+            None
+        };
 
-        /* Set the listener to non-blocking mode */
-        self.listener.set_nonblocking(true)?;
-        println!("Server-{}: Listener set to non-blocking mode.", id + 1);
+        self.stats.set_capacity(health::capacity(&self.config.get()));
+        self.stats.set_listening(true);
 
         /* 
             start runing th loop untill the is_runing variable is set to 
             false (i.e. the server is ordered to stop)
         */
-        while is_running[id].load(Ordering::SeqCst) {
+        while running.load(Ordering::SeqCst) {
             /*listen to any new connection on the server */
-            match self.listener.accept() {
-                Ok((stream, addr)) => {
-                    println!("Server-{}: New client connected: {}", id + 1, addr);
+            match self.listener.accept(ACCEPT_INTERVAL) {
+                Ok(Some(stream)) => {
+                    let addr = stream.peer();
+                    /* over the limit the client is disconnected right away */
+                    let slot = match admit(id, &addr, &self.config, &self.stats) {
+                        Some(slot) => slot,
+                        None => {
+                            let _ = stream.shutdown();
+                            continue;
+                        }
+                    };
+                    if let Some(worker_queue) = &worker_queue {
+                        if worker_queue.send((stream, slot)).is_err() {
+                            error!("Server-{}: No worker left to handle client {}", id + 1, addr);
+                        }
+                        continue;
+                    }
+
                     /*
                         clone the variable client_thread which is responible for tracking the threads 
                         so that we can at the end make sure that all threads are joined and finished 
                     */
                     let client_threads = Arc::clone(&self.client_threads);
-                    let is_running = Arc::clone(&IS_RUNNING.get().unwrap());
+                    let config = Arc::clone(&self.config);
+                    let recorder = self.recorder.clone();
+                    let audit = self.audit.clone();
+                    let running = Arc::clone(&running);
                     
                     /* 
                         Spawn a new thread to handle the client request as each client will be 
                         handled in an individual thread 
                     */
-                    let handle = thread::spawn(move || {
-                        /* create a new client and pass to it the stream  */
-                        let mut client = Client::new(stream);
-                        /* handle the client continously until the server is stoped */
-                        while is_running[id].load(Ordering::SeqCst) {
-                            if let Err(_e_) = client.handle(id) {
-                                println!("Server-{}: Error handling client {}", id + 1, id + 1);
-                                break;
-                            } else {
-                                println!("Server-{}: nothing to handle", id + 1);
-                                /* if there is nothing to handle then sleep to save cpu usage*/
-                                thread::sleep(Duration::from_millis(100));
-                            }
-                        }
-                    });
+                    let handle =
+                        thread::spawn(move || serve_client(stream, slot, id, &running, config, recorder, audit));
 
                     // Save the thread handle
                     client_threads.lock().unwrap().push(handle);
                 }
-                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                    // No incoming connections, sleep briefly to reduce CPU usage
-                    println!("Server-{}: No incoming connections, sleeping briefly...", id + 1);
-                    thread::sleep(Duration::from_millis(100));
-                }
+                Ok(None) => {}
                 Err(e) => {
                     error!("Server-{}: Error accepting connection: {}", id + 1, e);
                 }
             }
         }
         info!("Server-{} stopped.", id + 1);
-        /* stop all the threads */
-        self.stop_threads();
+        self.stats.set_listening(false);
+        self.stats.start_draining();
+        /* the workers leave once the queue is closed and empty */
+        drop(worker_queue);
+        /* stop all the threads, giving the clients up to `drain_timeout` to finish their request */
+        let drained = self.stop_threads();
+        self.flush_audit();
+        self.stats.set_stopped();
+        for endpoint_thread in endpoint_threads {
+            if let Err(e) = endpoint_thread.join() {
+                error!("Failed to join an endpoint thread: {:?}", e);
+            }
+        }
+        /*
+            arm a new flag for the next run, the threads left behind by an incomplete drain keep
+            the cleared one and still wind down
+        */
+        *self.running.lock().unwrap() = Arc::new(AtomicBool::new(true));
+        if !drained {
+            return Err(ServerError::Shutdown {
+                drain_timeout: self.config.get().drain_timeout.unwrap_or_default(),
+            });
+        }
         Ok(())
     }
 
-    // Stops the server by setting the `is_running` flag to `false`
-    pub fn stop(&self, id: usize) {
-        let is_running = IS_RUNNING.get().expect("Static vector not initialized");
-        if is_running[id].load(Ordering::SeqCst) {
-            is_running[id].store(false, Ordering::SeqCst);
-            println!("Server-{}: Shutdown signal sent.", id + 1);
+    /* Makes sure the requests served until now are in the audit log once `run` returns */
+    fn flush_audit(&self) {
+        if let Some(audit) = &self.audit {
+            audit.flush();
+        }
+    }
+
+    /*
+        Stops the server: `run` stops accepting clients, lets the connected ones finish their
+        request and returns. Called before `run`, the next `run` returns right away.
+    */
+    pub fn stop(&self) {
+        /* not ready anymore from now on, even before the accept loop notices */
+        self.stats.start_draining();
+        if self.running.lock().unwrap().swap(false, Ordering::SeqCst) {
+            info!("Shutdown signal sent to the server on {}.", self.describe());
         } else {
-            warn!("Server-{}: Server was already stopped or not running.", id + 1);
+            warn!("The server on {} was already stopped.", self.describe());
         }
     }
 
-    fn stop_threads(&self) {
+    /* Names the server in the logs that don't know its id */
+    fn describe(&self) -> String {
+        self.listener.describe()
+    }
+
+    /*
+        Starts `config.workers` threads serving the clients pushed in the returned queue.
+        An idle worker takes the next client waiting, a busy one also takes the queued clients
+        and serves all of its clients in turns, so a worker never keeps a client waiting just
+        because another one is still connected.
+    */
+    fn start_workers(&self, id: usize, running: &Arc<AtomicBool>) -> Sender<(A::Stream, ConnectionSlot)> {
+        let (sender, receiver) = mpsc::channel::<(A::Stream, ConnectionSlot)>();
+        let receiver = Arc::new(Mutex::new(receiver));
         let mut client_threads = self.client_threads.lock().unwrap();
+        let workers = self.config.get().workers;
+
+        for _ in 0..workers {
+            let receiver = Arc::clone(&receiver);
+            let config = Arc::clone(&self.config);
+            let recorder = self.recorder.clone();
+            let audit = self.audit.clone();
+            let running = Arc::clone(running);
+            client_threads.push(thread::spawn(move || {
+                let mut clients: Vec<ServedClient<A::Stream>> = Vec::new();
+                loop {
+                    /*
+                        an idle worker waits for a client, a busy one only looks whether one is
+                        queued (the lock is never held while serving the clients)
+                    */
+                    let next = if clients.is_empty() {
+                        receiver.lock().unwrap().recv_timeout(POLL_INTERVAL)
+                    } else {
+                        match receiver.try_lock() {
+                            Ok(receiver) => receiver.try_recv().map_err(|e| match e {
+                                TryRecvError::Empty => RecvTimeoutError::Timeout,
+                                TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+                            }),
+                            Err(_) => Err(RecvTimeoutError::Timeout),
+                        }
+                    };
+                    match next {
+                        Ok((stream, slot)) => {
+                            clients.push(ServedClient::open(
+                                stream,
+                                slot,
+                                id,
+                                &config,
+                                recorder.as_ref(),
+                                audit.as_ref(),
+                            ));
+                            share_poll_interval(&clients);
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        /* the server stopped accepting, finish the clients we have */
+                        Err(RecvTimeoutError::Disconnected) if clients.is_empty() => break,
+                        Err(RecvTimeoutError::Disconnected) => {}
+                    }
+
+                    /* serve the clients in turns, dropping the ones that are done */
+                    let served = clients.len();
+                    clients.retain_mut(|client| client.step(id, &running, &config));
+                    if clients.len() != served {
+                        share_poll_interval(&clients);
+                    }
+                }
+            }));
+        }
+        debug!("Server-{}: {} workers started.", id + 1, workers);
+        sender
+    }
+
+    /*
+        Joins the client threads. Returns false if some of them were still running when the
+        drain timeout expired, those are left behind (detached).
+    */
+    fn stop_threads(&self) -> bool {
+        let mut client_threads = self.client_threads.lock().unwrap();
+        let deadline = self.config.get().drain_timeout.map(|drain_timeout| Instant::now() + drain_timeout);
+
+        if let Some(deadline) = deadline {
+            /* JoinHandle has no join with a timeout, wait for the threads to finish on their own */
+            while client_threads.iter().any(|handle| !handle.is_finished()) && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        let mut drained = true;
         while let Some(handle) = client_threads.pop() {
+            if deadline.is_some() && !handle.is_finished() {
+                drained = false;
+                continue;
+            }
             if let Err(e) = handle.join() {
-                eprintln!("Failed to join client thread: {:?}", e);
+                error!("Failed to join client thread: {:?}", e);
             }
         }
-        println!("All client threads have been joined.");
+        if drained {
+            debug!("All client threads have been joined.");
+        } else {
+            warn!("Drain timeout expired, some client threads are still running.");
+        }
+        drained
     }
-}
\ No newline at end of file
+}
+
+/*
+    Handles the client continously until it leaves or the server is stoped, no need to sleep
+    between two calls as the read itself waits for data. A request already in flight when the
+    server stops is still completed, unless the client stalls in the middle of it for
+    DRAIN_STALL_LIMIT after the stop. Reloaded settings are picked up between two calls.
+*/
+fn serve_client<S: Transport>(
+    stream: S,
+    slot: ConnectionSlot,
+    id: usize,
+    running: &AtomicBool,
+    config: Arc<LiveConfig>,
+    recorder: Option<Arc<Recorder>>,
+    audit: Option<Arc<AuditLog>>,
+) {
+    let mut client = ServedClient::open(stream, slot, id, &config, recorder.as_ref(), audit.as_ref());
+    while client.step(id, running, &config) {}
+}
+
+/*
+    Admits a new connection of any transport against `max_connections`, logging the ones
+    refused. The connection is counted as active until the returned slot is dropped.
+*/
+pub(crate) fn admit(id: usize, peer: &str, config: &LiveConfig, stats: &Arc<ServerStats>) -> Option<ConnectionSlot> {
+    let max_connections = config.get().max_connections;
+    if !stats.try_connection_opened(max_connections.map(|max| max as u64)) {
+        warn!(
+            "Server-{}: Rejecting client {}, already {} clients connected.",
+            id + 1,
+            peer,
+            max_connections.unwrap_or_default()
+        );
+        return None;
+    }
+    Some(ConnectionSlot {
+        stats: Arc::clone(stats),
+    })
+}
+
+/* An admitted connection, counted as active until dropped */
+pub(crate) struct ConnectionSlot {
+    stats: Arc<ServerStats>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.stats.connection_closed();
+    }
+}
+
+/* A connection as one of the server threads serves it */
+struct ServedClient<S: Transport> {
+    client: Client<S>,
+    slot: ConnectionSlot, // released when the client is dropped
+    connection: logging::Connection, // what the records logged for the connection are tagged with
+    generation: u64, // of the settings the client applies
+    stopped_at: Option<Instant>, // when we noticed the server was stopped
+}
+
+impl<S: Transport> ServedClient<S> {
+    fn open(
+        stream: S,
+        slot: ConnectionSlot,
+        id: usize,
+        config: &LiveConfig,
+        recorder: Option<&Arc<Recorder>>,
+        audit: Option<&Arc<AuditLog>>,
+    ) -> Self {
+        let peer = stream.peer();
+        let span = logging::connection_span(&peer);
+        debug!("Server-{}: New client connected: {}", id + 1, peer);
+        let generation = config.generation();
+        let mut client = Client::with_config(stream, config.get(), Arc::clone(&slot.stats));
+        if let Some(recorder) = recorder {
+            client.record_to(recorder.session(&peer));
+        }
+        if let Some(audit) = audit {
+            client.audit_to(audit.connection(span.id(), &peer));
+        }
+        ServedClient {
+            client,
+            slot,
+            connection: logging::Connection { id: span.id(), peer },
+            generation,
+            stopped_at: None,
+        }
+    }
+
+    /* Serves the client for one poll interval, returns false once the connection is done */
+    fn step(&mut self, id: usize, running: &AtomicBool, config: &LiveConfig) -> bool {
+        let _span = logging::enter(&self.connection);
+        if self.client.is_closed() {
+            return false;
+        }
+        if !running.load(Ordering::SeqCst) {
+            if !self.client.is_busy() {
+                return false;
+            }
+            let stopped_at = *self.stopped_at.get_or_insert_with(Instant::now);
+            if self.client.idle_for().min(stopped_at.elapsed()) >= DRAIN_STALL_LIMIT {
+                warn!(
+                    "Server-{}: Client {} stalled in the middle of a request during the shutdown, closing it.",
+                    id + 1,
+                    self.connection.peer
+                );
+                return false;
+            }
+        }
+        if config.generation() != self.generation {
+            self.generation = config.generation();
+            self.client.set_config(config.get());
+        }
+        if let Err(e) = self.client.handle(id) {
+            /* timeouts and framing errors were counted where they happened */
+            if let ServerError::Io(_) = e {
+                self.slot.stats.record_error("io");
+            }
+            debug!("Server-{}: Closing client {}: {}", id + 1, self.connection.peer, e);
+            return false;
+        }
+        true
+    }
+}
+
+/*
+    A worker serving several clients waits for each one only for SHARED_POLL_INTERVAL so a
+    round over all of them stays short, a single client gets the usual POLL_INTERVAL.
+*/
+fn share_poll_interval<S: Transport>(clients: &[ServedClient<S>]) {
+    let interval = if clients.len() > 1 { SHARED_POLL_INTERVAL } else { POLL_INTERVAL };
+    for served in clients {
+        served.client.set_poll_interval(interval);
+    }
+}
+
+/* True when `deadline_unix_ms` is set and the wall clock is past it */
+fn deadline_passed(deadline_unix_ms: u64) -> bool {
+    let now_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    deadline_unix_ms != 0 && now_unix_ms >= deadline_unix_ms
+}
+
+/* Binds a listener on `addr`, logging the reason when the bind fails */
+fn bind(addr: &str) -> io::Result<TcpListener> {
+    match TcpListener::bind(addr) {
+        Ok(listener) => {
+            info!("The Server is initialized and listening on {}", addr);
+            Ok(listener)
+        }
+        Err(e) => {
+            // Log different error cases
+            match e.kind() {
+                ErrorKind::AddrInUse => {
+                    error!("The address {} is already in use.", addr);
+                }
+                ErrorKind::PermissionDenied => {
+                    error!("Permission denied to bind to address: {}", addr);
+                }
+                _ => {
+                    error!("Error binding to address {}: {}", addr, e);
+                }
+            }
+            // Return the error if binding fails
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use crate::health::Health;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/*
+    Upper bounds of the buckets of the request latency histogram, the last bucket takes
+    everything above. Most requests are served in microseconds, the upper buckets catch
+    the ones that waited (a slow client, a loaded machine).
+*/
+pub const LATENCY_BUCKETS: [Duration; 14] = [
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+/*
+    Counters shared by the server and all of its connection threads.
+    Apart from the number of active connections they only ever go up,
+    a snapshot can be taken at any time with `snapshot`.
+    The connections are the ones of the TCP and WebSocket listeners plus one per HTTP request,
+    the bytes only the TCP and WebSocket ones. The requests and the errors are counted whatever
+    way the request came in (TCP, WebSocket, HTTP). The uptime
+    runs from the creation of the counters, that is the creation of the server.
+    They also carry the state of the server the health checks are answered from, so that
+    every thread answering a HealthCheck sees the same one.
+*/
+#[derive(Debug)]
+pub struct ServerStats {
+    started: Instant,
+    accepted_connections: AtomicU64,
+    active_connections: AtomicU64,
+    rejected_connections: AtomicU64,
+    read_timeouts: AtomicU64,
+    write_timeouts: AtomicU64,
+    request_timeouts: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    requests: Mutex<BTreeMap<&'static str, u64>>, // by message type
+    errors: Mutex<BTreeMap<&'static str, u64>>, // by kind
+    latency: Histogram,
+    listening: AtomicBool, // the accept loop is running
+    draining: AtomicBool, // a stop was requested, the clients are finishing their requests
+    stopped: AtomicBool, // `run` is done
+    capacity: AtomicU64, // how many clients can be served at once, 0 for no limit
+}
+
+/* A point in time copy of the ServerStats counters */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub uptime: Duration,
+    pub accepted_connections: u64,
+    pub active_connections: u64,
+    pub rejected_connections: u64,
+    pub read_timeouts: u64,
+    pub write_timeouts: u64,
+    pub request_timeouts: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    /* requests served by message type ("echo_message"), "invalid" for the ones that did not decode */
+    pub requests: BTreeMap<String, u64>,
+    /* error responses by error code ("overflow"), timeouts ("read_timeout") and broken connections ("io") */
+    pub errors: BTreeMap<String, u64>,
+    pub latency: LatencySnapshot,
+}
+
+/* How long the requests took, from their first byte received to their response ready */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    /* how many requests took at most each of LATENCY_BUCKETS (cumulative, like Prometheus) */
+    pub buckets: Vec<(Duration, u64)>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+/* The different timeouts a connection can run into */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+    Read,
+    Write,
+    Request,
+}
+
+impl TimeoutKind {
+    /* The name of the timeout in the error counts */
+    pub fn error_kind(self) -> &'static str {
+        match self {
+            TimeoutKind::Read => "read_timeout",
+            TimeoutKind::Write => "write_timeout",
+            TimeoutKind::Request => "request_timeout",
+        }
+    }
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        ServerStats {
+            started: Instant::now(),
+            accepted_connections: AtomicU64::default(),
+            active_connections: AtomicU64::default(),
+            rejected_connections: AtomicU64::default(),
+            read_timeouts: AtomicU64::default(),
+            write_timeouts: AtomicU64::default(),
+            request_timeouts: AtomicU64::default(),
+            bytes_received: AtomicU64::default(),
+            bytes_sent: AtomicU64::default(),
+            requests: Mutex::default(),
+            errors: Mutex::default(),
+            latency: Histogram::default(),
+            listening: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            capacity: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ServerStats {
+    /*
+        Counts a new connection unless `max` connections are already active, then it is counted
+        as rejected instead. Returns whether the connection was admitted. The check and the count
+        are one step, so acceptors running side by side can't go over `max` together.
+    */
+    pub fn try_connection_opened(&self, max: Option<u64>) -> bool {
+        let admitted = self
+            .active_connections
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |active| match max {
+                Some(max) if active >= max => None,
+                _ => Some(active + 1),
+            })
+            .is_ok();
+        if admitted {
+            self.accepted_connections.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.record_rejected();
+        }
+        admitted
+    }
+
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn record_rejected(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_timeout(&self, kind: TimeoutKind) {
+        let counter = match kind {
+            TimeoutKind::Read => &self.read_timeouts,
+            TimeoutKind::Write => &self.write_timeouts,
+            TimeoutKind::Request => &self.request_timeouts,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.record_error(kind.error_kind());
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /*
+        Counts a request answered with `outcome` (see ServerMessage::outcome), anything but
+        "ok" is also counted as an error of that kind.
+    */
+    pub fn record_request(&self, message_type: &'static str, outcome: &'static str, latency: Duration) {
+        *self.requests.lock().unwrap().entry(message_type).or_default() += 1;
+        if outcome != "ok" {
+            self.record_error(outcome);
+        }
+        self.latency.observe(latency);
+    }
+
+    pub fn record_error(&self, kind: &'static str) {
+        *self.errors.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    /* A new run of the server starts, it is live again */
+    pub fn start_serving(&self) {
+        self.stopped.store(false, Ordering::SeqCst);
+        self.draining.store(false, Ordering::SeqCst);
+    }
+
+    pub fn set_listening(&self, listening: bool) {
+        self.listening.store(listening, Ordering::SeqCst);
+    }
+
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn set_stopped(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /* How many clients can be served at once (None for no limit), above it the server is saturated */
+    pub fn set_capacity(&self, capacity: Option<usize>) {
+        self.capacity.store(capacity.unwrap_or(0) as u64, Ordering::Relaxed);
+    }
+
+    /* Whether the server is live and ready to take more clients, see Health */
+    pub fn health(&self) -> Health {
+        let listening = self.listening.load(Ordering::SeqCst);
+        let draining = self.draining.load(Ordering::SeqCst);
+        let active_connections = self.active_connections();
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let saturated = capacity > 0 && active_connections >= capacity;
+        Health {
+            live: !self.stopped.load(Ordering::SeqCst),
+            ready: listening && !draining && !saturated,
+            listening,
+            draining,
+            saturated,
+            active_connections,
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let by_name = |counts: &Mutex<BTreeMap<&'static str, u64>>| {
+            counts
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect()
+        };
+        StatsSnapshot {
+            uptime: self.started.elapsed(),
+            accepted_connections: self.accepted_connections.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            rejected_connections: self.rejected_connections.load(Ordering::Relaxed),
+            read_timeouts: self.read_timeouts.load(Ordering::Relaxed),
+            write_timeouts: self.write_timeouts.load(Ordering::Relaxed),
+            request_timeouts: self.request_timeouts.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            requests: by_name(&self.requests),
+            errors: by_name(&self.errors),
+            latency: self.latency.snapshot(),
+        }
+    }
+}
+
+/* Counts of a histogram over LATENCY_BUCKETS, the last slot is for everything above */
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound, cumulative)
+            })
+            .collect();
+        LatencySnapshot {
+            buckets,
+            count: cumulative + self.buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed),
+            sum: Duration::from_micros(self.sum_us.load(Ordering::Relaxed)),
+        }
+    }
+}
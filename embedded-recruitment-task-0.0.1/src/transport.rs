@@ -0,0 +1,310 @@
+/*
+    What the server needs from a byte stream and from the source of its connections, and an
+    in-memory implementation of both next to TCP.
+
+        let (client_end, server_end) = transport::duplex(64 * 1024);
+        let mut handler = server::Client::new(server_end);
+
+        let listener = MemoryListener::new(64 * 1024);
+        let server = Server::with_acceptor(listener.clone(), ServerConfig::default());
+        let client_end = listener.connect();
+
+    The in-memory pipe lets the tests drive a connection without binding any port: whatever is
+    written on one end is read on the other, dropping an end is seen as the peer disconnecting.
+*/
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/* A byte stream the server can serve a client over */
+pub trait Transport: Read + Write + Send {
+    /* Describes the other end for the logs and the recordings */
+    fn peer(&self) -> String;
+
+    /*
+        Makes `read` and `write` block at most `timeout` before failing with WouldBlock or
+        TimedOut, so the handler gets a chance to check its own timeouts.
+    */
+    fn set_poll_interval(&self, timeout: Duration) -> io::Result<()>;
+
+    /* Closes both directions, the peer reads the end of the stream */
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+impl Transport for TcpStream {
+    fn peer(&self) -> String {
+        self.peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "unknown peer".to_string())
+    }
+
+    fn set_poll_interval(&self, timeout: Duration) -> io::Result<()> {
+        /*
+            the accepted socket may inherit the non-blocking mode of the listener (Windows does that),
+            use a blocking socket with short timeouts so the handler never parks forever in `read`
+            or `write`. Both modes end up the same way for us anyway: WouldBlock means "try later".
+        */
+        self.set_nonblocking(false)?;
+        self.set_read_timeout(Some(timeout))?;
+        self.set_write_timeout(Some(timeout))
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/* Where a server gets the connections it serves from */
+pub trait Acceptor: Send + Sync {
+    type Stream: Transport + 'static;
+
+    /*
+        Returns the next connection, waiting at most `timeout` for one so the server gets a
+        chance to notice it was stopped. None when no connection came in time.
+    */
+    fn accept(&self, timeout: Duration) -> io::Result<Option<Self::Stream>>;
+
+    /* Names where the connections come from in the logs */
+    fn describe(&self) -> String;
+}
+
+impl Acceptor for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept(&self, timeout: Duration) -> io::Result<Option<TcpStream>> {
+        /* a blocking accept would never look at the running flag again */
+        self.set_nonblocking(true)?;
+        match TcpListener::accept(self) {
+            Ok((stream, _)) => Ok(Some(stream)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                /* no incoming connections, sleep briefly to reduce CPU usage */
+                thread::sleep(timeout);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.local_addr()
+            .map_or_else(|_| "an unknown address".to_string(), |addr| addr.to_string())
+    }
+}
+
+/*
+    Creates a connected pair of in-memory streams, each direction buffers up to `capacity`
+    bytes before `write` blocks.
+*/
+pub fn duplex(capacity: usize) -> (MemoryStream, MemoryStream) {
+    let (a_to_b, b_to_a) = (Arc::new(Pipe::new(capacity)), Arc::new(Pipe::new(capacity)));
+    (
+        MemoryStream::new("memory-a", Arc::clone(&b_to_a), Arc::clone(&a_to_b)),
+        MemoryStream::new("memory-b", a_to_b, b_to_a),
+    )
+}
+
+/* One end of an in-memory duplex pipe, `read` and `write` block until they can make progress */
+pub struct MemoryStream {
+    name: &'static str,
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+    timeout: Mutex<Option<Duration>>,
+}
+
+impl MemoryStream {
+    fn new(name: &'static str, incoming: Arc<Pipe>, outgoing: Arc<Pipe>) -> Self {
+        MemoryStream {
+            name,
+            incoming,
+            outgoing,
+            timeout: Mutex::new(None),
+        }
+    }
+
+    /* Makes `read` and `write` fail with WouldBlock after `timeout`, None blocks for ever */
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        *self.timeout.lock().unwrap() = timeout;
+    }
+
+    /* How many bytes were written by the peer and not read yet */
+    pub fn available(&self) -> usize {
+        self.incoming.state().data.len()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        *self.timeout.lock().unwrap()
+    }
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.incoming.wait(self.timeout(), |state| {
+            !state.data.is_empty() || state.writer_closed || state.reader_closed
+        })?;
+        if state.reader_closed {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "the stream was shut down"));
+        }
+        let read = buffer.len().min(state.data.len());
+        for (byte, data) in buffer.iter_mut().zip(state.data.drain(..read)) {
+            *byte = data;
+        }
+        drop(state);
+        self.incoming.changed.notify_all();
+        Ok(read)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let capacity = self.outgoing.capacity;
+        let mut state = self.outgoing.wait(self.timeout(), |state| {
+            state.data.len() < capacity || state.reader_closed || state.writer_closed
+        })?;
+        if state.reader_closed || state.writer_closed {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "the peer closed the stream"));
+        }
+        let written = buffer.len().min(capacity - state.data.len());
+        state.data.extend(&buffer[..written]);
+        drop(state);
+        self.outgoing.changed.notify_all();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MemoryStream {
+    fn peer(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn set_poll_interval(&self, timeout: Duration) -> io::Result<()> {
+        self.set_timeout(Some(timeout));
+        Ok(())
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.incoming.close(|state| state.reader_closed = true);
+        self.outgoing.close(|state| state.writer_closed = true);
+        Ok(())
+    }
+}
+
+impl Drop for MemoryStream {
+    fn drop(&mut self) {
+        let _ = Transport::shutdown(self);
+    }
+}
+
+/*
+    Connections without sockets: every `connect` creates a duplex pipe and queues its server end
+    until the server accepts it. The clones share the same queue, one is given to the server and
+    the others connect to it.
+*/
+#[derive(Clone)]
+pub struct MemoryListener {
+    capacity: usize,
+    pending: Arc<(Mutex<VecDeque<MemoryStream>>, Condvar)>,
+}
+
+impl MemoryListener {
+    /* `capacity` is the buffer of each direction of the pipes, see duplex */
+    pub fn new(capacity: usize) -> Self {
+        MemoryListener {
+            capacity,
+            pending: Arc::new((Mutex::new(VecDeque::new()), Condvar::new())),
+        }
+    }
+
+    /* Opens a connection to the server accepting from this listener, returns the client end */
+    pub fn connect(&self) -> MemoryStream {
+        let (client_end, server_end) = duplex(self.capacity);
+        let (queue, arrived) = &*self.pending;
+        queue.lock().unwrap().push_back(server_end);
+        arrived.notify_one();
+        client_end
+    }
+}
+
+impl Acceptor for MemoryListener {
+    type Stream = MemoryStream;
+
+    fn accept(&self, timeout: Duration) -> io::Result<Option<MemoryStream>> {
+        let (queue, arrived) = &*self.pending;
+        let (mut queue, _) = arrived
+            .wait_timeout_while(queue.lock().unwrap(), timeout, |queue| queue.is_empty())
+            .unwrap();
+        Ok(queue.pop_front())
+    }
+
+    fn describe(&self) -> String {
+        "memory".to_string()
+    }
+}
+
+/* One direction of a duplex pipe */
+struct Pipe {
+    capacity: usize,
+    state: Mutex<PipeState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct PipeState {
+    data: VecDeque<u8>,
+    /* nothing more will be written, the reader gets the end of the stream once `data` is read */
+    writer_closed: bool,
+    /* nothing more will be read, the writer gets BrokenPipe */
+    reader_closed: bool,
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Self {
+        Pipe {
+            capacity: capacity.max(1),
+            state: Mutex::new(PipeState::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, PipeState> {
+        self.state.lock().unwrap()
+    }
+
+    /* Waits until `ready` holds, failing with WouldBlock once `timeout` expired */
+    fn wait(&self, timeout: Option<Duration>, ready: impl Fn(&PipeState) -> bool) -> io::Result<MutexGuard<'_, PipeState>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state();
+        while !ready(&state) {
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::Error::new(io::ErrorKind::WouldBlock, "the stream is not ready"));
+                    }
+                    self.changed.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => self.changed.wait(state).unwrap(),
+            };
+        }
+        Ok(state)
+    }
+
+    fn close(&self, close: impl FnOnce(&mut PipeState)) {
+        close(&mut self.state());
+        self.changed.notify_all();
+    }
+}
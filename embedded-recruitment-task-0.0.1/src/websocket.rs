@@ -0,0 +1,227 @@
+use crate::{
+    audit::{AuditLog, AuditSession},
+    logging, message,
+    server::{self, LiveConfig},
+    stats::ServerStats,
+};
+use log::{debug, error, info, warn};
+use prost::Message as _;
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    HandshakeError, Message, WebSocket,
+};
+
+/* How long a blocking WebSocket read waits before checking whether the server was stopped */
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/*
+    Accepts WebSocket clients on `listener` until `running` is cleared (the server is stopped).
+    Each client gets its own thread, like the TCP clients, and all of them are joined
+    before this function returns. They are counted with the TCP clients and share their
+    max_connections limit.
+*/
+pub(crate) fn serve(
+    listener: TcpListener,
+    id: usize,
+    running: &Arc<AtomicBool>,
+    config: Arc<LiveConfig>,
+    stats: Arc<ServerStats>,
+    audit: Option<Arc<AuditLog>>,
+) {
+    let mut connections: Vec<JoinHandle<()>> = Vec::new();
+
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                /* over the limit the client is disconnected right away */
+                let slot = match server::admit(id, &addr.to_string(), &config, &stats) {
+                    Some(slot) => slot,
+                    None => {
+                        let _ = stream.shutdown(Shutdown::Both);
+                        continue;
+                    }
+                };
+                let config = Arc::clone(&config);
+                let stats = Arc::clone(&stats);
+                let audit = audit.clone();
+                let running = Arc::clone(running);
+                connections.push(thread::spawn(move || {
+                    let span = logging::connection_span(&addr.to_string());
+                    debug!("Server-{}: New WebSocket client connected: {}", id + 1, addr);
+                    let audit = audit.map(|audit| audit.connection(span.id(), &addr.to_string()));
+                    if let Err(e) = handle_connection(stream, addr, id, &running, &config, &stats, audit.as_ref()) {
+                        warn!("Server-{}: WebSocket client {} failed: {}", id + 1, addr, e);
+                    }
+                    drop(slot);
+                }));
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(e) => {
+                error!("Server-{}: Error accepting WebSocket connection: {}", id + 1, e);
+            }
+        }
+        /* forget about the clients that already left */
+        connections.retain(|handle| !handle.is_finished());
+    }
+
+    for handle in connections {
+        if let Err(e) = handle.join() {
+            error!("Failed to join WebSocket client thread: {:?}", e);
+        }
+    }
+    info!("Server-{}: WebSocket listener stopped.", id + 1);
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    id: usize,
+    running: &AtomicBool,
+    config: &LiveConfig,
+    stats: &ServerStats,
+    audit: Option<&AuditSession>,
+) -> io::Result<()> {
+    /*
+        the accepted socket may inherit the non-blocking mode of the listener, switch it back
+        to blocking with a short timeout so the loop below can still notice a server stop
+    */
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let mut websocket = match accept(CountedStream { stream, stats }, id, running) {
+        Some(websocket) => websocket,
+        None => return Ok(()),
+    };
+
+    while running.load(Ordering::SeqCst) {
+        match websocket.read() {
+            Ok(Message::Binary(payload)) => {
+                let client_message = match message::ClientMessage::decode(payload.as_ref()) {
+                    Ok(client_message) => client_message,
+                    Err(e) => {
+                        warn!("Server-{}: Invalid ClientMessage from {}: {}", id + 1, addr, e);
+                        stats.record_error("invalid_request");
+                        if let Some(audit) = audit {
+                            audit.request("invalid", "invalid_request", Duration::ZERO, payload.len());
+                        }
+                        close(&mut websocket, CloseCode::Invalid, "invalid ClientMessage");
+                        return Ok(());
+                    }
+                };
+                let started = Instant::now();
+                let message_type = client_message.message_type();
+                let server_message = server::dispatch(client_message, id, &config.services(), stats);
+                let latency = started.elapsed();
+                stats.record_request(message_type, server_message.outcome(), latency);
+                if let Some(audit) = audit {
+                    audit.request(message_type, server_message.outcome(), latency, payload.len());
+                }
+                debug!(
+                    message_type = message_type,
+                    outcome = server_message.outcome(),
+                    latency_us = latency.as_micros() as u64,
+                    request_bytes = payload.len();
+                    "Server-{}: WebSocket request served.",
+                    id + 1
+                );
+                websocket
+                    .send(Message::binary(server_message.encode_to_vec()))
+                    .map_err(into_io_error)?;
+            }
+            Ok(Message::Text(_)) => {
+                warn!("Server-{}: Text message from {} is not supported.", id + 1, addr);
+                close(&mut websocket, CloseCode::Unsupported, "only binary messages are supported");
+                return Ok(());
+            }
+            /* ping/pong are answered by tungstenite itself, close is acknowledged on the next read */
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(ref e))
+                if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+            Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => {
+                debug!("Server-{}: WebSocket client {} disconnected.", id + 1, addr);
+                return Ok(());
+            }
+            Err(e) => return Err(into_io_error(e)),
+        }
+    }
+
+    /* the server is stopping, say goodbye properly */
+    close(&mut websocket, CloseCode::Away, "server is shutting down");
+    Ok(())
+}
+
+/* Runs the opening handshake, retrying when the short read timeout interrupts it */
+fn accept<'a>(stream: CountedStream<'a>, id: usize, running: &AtomicBool) -> Option<WebSocket<CountedStream<'a>>> {
+    let mut result = tungstenite::accept(stream);
+    loop {
+        match result {
+            Ok(websocket) => return Some(websocket),
+            Err(HandshakeError::Interrupted(mid_handshake)) => {
+                if !running.load(Ordering::SeqCst) {
+                    return None;
+                }
+                result = mid_handshake.handshake();
+            }
+            Err(HandshakeError::Failure(e)) => {
+                warn!("Server-{}: WebSocket handshake failed: {}", id + 1, e);
+                return None;
+            }
+        }
+    }
+}
+
+fn close(websocket: &mut WebSocket<CountedStream<'_>>, code: CloseCode, reason: &'static str) {
+    let frame = CloseFrame {
+        code,
+        reason: reason.into(),
+    };
+    if websocket.close(Some(frame)).is_ok() {
+        /* flush the close frame, the peer answer is not waited for */
+        let _ = websocket.flush();
+    }
+}
+
+/* The socket of a WebSocket client, its bytes are counted with those of the TCP clients */
+struct CountedStream<'a> {
+    stream: TcpStream,
+    stats: &'a ServerStats,
+}
+
+impl Read for CountedStream<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.stream.read(buffer)?;
+        self.stats.record_received(read);
+        Ok(read)
+    }
+}
+
+impl Write for CountedStream<'_> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let written = self.stream.write(buffer)?;
+        self.stats.record_sent(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn into_io_error(e: tungstenite::Error) -> io::Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
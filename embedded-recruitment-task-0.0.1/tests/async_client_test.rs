@@ -0,0 +1,193 @@
+#![cfg(feature = "async")]
+
+use embedded_recruitment_task::{
+    async_client::AsyncClient,
+    client::{ClientConfig, ClientError, ConnectError},
+    framing,
+    message::{client_message, server_message, ClientMessage, EchoMessage, ServerMessage},
+    server::ServerConfig,
+};
+use prost::Message;
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpListener},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+mod common;
+
+/*
+    A server answering the echo requests of one connection in order, waiting `delay` before
+    answering the requests whose content is "slow"
+*/
+fn start_slow_echo_server(delay: Duration) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        while let Ok(frame) = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE) {
+            let content = match ClientMessage::decode(frame.as_slice()).unwrap().message {
+                Some(client_message::Message::EchoMessage(echo)) => echo.content,
+                other => panic!("Unexpected request {:?}", other),
+            };
+            if content == "slow" {
+                thread::sleep(delay);
+            }
+            let response = ServerMessage {
+                message: Some(server_message::Message::EchoMessage(EchoMessage { content })),
+            };
+            if stream.write_all(&framing::encode_frame(&response)).is_err() {
+                break;
+            }
+        }
+    });
+    (addr, handle)
+}
+
+#[tokio::test]
+async fn test_pipelined_requests() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+    let client = AsyncClient::connect(addr).await.unwrap();
+    assert_eq!(client.echo("hello").await.unwrap(), "hello");
+    assert_eq!(client.add(1, 2).await.unwrap(), 3);
+
+    // Many requests in flight at once on the one connection, each gets its own response
+    let (hello, sum) = tokio::join!(client.echo("joined"), client.add(20, 22));
+    assert_eq!(hello.unwrap(), "joined");
+    assert_eq!(sum.unwrap(), 42);
+
+    let tasks: Vec<_> = (0..100)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                if i % 2 == 0 {
+                    assert_eq!(client.add(i, i).await.unwrap(), 2 * i);
+                } else {
+                    assert_eq!(client.echo(&i.to_string()).await.unwrap(), i.to_string());
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("A request task panicked");
+    }
+    assert!(!client.is_closed());
+
+    // Joining blocks, the connection tasks need the runtime to close the connection meanwhile
+    drop(client);
+    let joined = tokio::task::spawn_blocking(move || {
+        server.stop();
+        handle.join()
+    });
+    assert!(joined.await.unwrap().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[tokio::test]
+async fn test_dropped_request_keeps_the_connection_in_sync() {
+    let (addr, handle) = start_slow_echo_server(Duration::from_millis(300));
+    let client = AsyncClient::connect(addr).await.unwrap();
+
+    // Give up on a request while the server is still working on it
+    let abandoned = tokio::time::timeout(Duration::from_millis(50), client.echo("slow")).await;
+    assert!(abandoned.is_err(), "The slow request should not have been answered yet");
+
+    // The response of the abandoned request is not handed to the next one
+    assert_eq!(client.echo("next").await.unwrap(), "next");
+
+    // The same goes for a request timing out through the read timeout of the config
+    let config = ClientConfig {
+        read_timeout: Some(Duration::from_millis(50)),
+        ..ClientConfig::default()
+    };
+    drop(client);
+    tokio::task::spawn_blocking(move || handle.join().unwrap()).await.unwrap();
+    let (addr, handle) = start_slow_echo_server(Duration::from_millis(300));
+    let client = AsyncClient::with_config(addr, config).await.unwrap();
+    match client.echo("slow").await {
+        Err(ClientError::Timeout(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+        other => panic!("Expected a timeout, got {:?}", other),
+    }
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(client.echo("after").await.unwrap(), "after");
+
+    drop(client);
+    tokio::task::spawn_blocking(move || handle.join().unwrap()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_requests_fail_once_the_server_is_gone() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let closing = thread::spawn(move || drop(listener.accept().unwrap()));
+
+    let client = AsyncClient::connect(addr).await.unwrap();
+    closing.join().unwrap();
+
+    let error = client.echo("anyone?").await.unwrap_err();
+    assert!(error.is_connection_error(), "Unexpected error {}", error);
+    assert!(client.is_closed());
+    assert!(client.add(1, 2).await.unwrap_err().is_connection_error());
+}
+
+#[tokio::test]
+async fn test_request_deadline() {
+    let (addr, handle) = start_slow_echo_server(Duration::from_millis(300));
+    let client = AsyncClient::connect(addr).await.unwrap();
+
+    let request = client_message::Message::EchoMessage(EchoMessage {
+        content: "slow".to_string(),
+    });
+    match client.call_within(request, Duration::from_millis(50)).await {
+        Err(ClientError::Timeout(_)) => {}
+        other => panic!("Expected a timeout, got {:?}", other),
+    }
+    // Giving up at the deadline leaves the connection usable
+    assert_eq!(client.echo("next").await.unwrap(), "next");
+
+    drop(client);
+    tokio::task::spawn_blocking(move || handle.join().unwrap()).await.unwrap();
+}
+
+/* An address nobody listens on: a port that was just released */
+fn refused_addr(ip: &str) -> SocketAddr {
+    TcpListener::bind((ip, 0))
+        .map(|listener| listener.local_addr().unwrap())
+        .unwrap_or_else(|_| SocketAddr::new(ip.parse().unwrap(), 1))
+}
+
+#[tokio::test]
+async fn test_connect_tries_every_address() {
+    let (server, addr, handle) = common::start_server(4, ServerConfig::default(), |_| {});
+    let config = ClientConfig {
+        connect_timeout: Some(Duration::from_secs(3)),
+        attempt_delay: Duration::from_millis(100),
+        ..ClientConfig::default()
+    };
+
+    // The server is the last address, after an IPv6 one and another one refusing, as for the sync client
+    let addrs = [refused_addr("::1"), refused_addr("127.0.0.1"), addr];
+    let client = AsyncClient::with_config(&addrs[..], config.clone())
+        .await
+        .expect("Failed to connect to the server");
+    assert_eq!(client.peer_addr(), addr);
+    assert_eq!(client.add(2, 3).await.unwrap(), 5);
+    drop(client);
+
+    // When no address accepts, every attempt is reported
+    let addrs = [refused_addr("::1"), refused_addr("127.0.0.1"), refused_addr("127.0.0.1")];
+    let error = match AsyncClient::with_config(&addrs[..], config).await {
+        Err(ClientError::Io(e)) => e,
+        Err(e) => panic!("Expected a connection error, got {:?}", e),
+        Ok(_) => panic!("Expected a connection error, got a connection"),
+    };
+    let attempts = &error.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()).unwrap().attempts;
+    let mut attempted: Vec<SocketAddr> = attempts.iter().map(|(addr, _)| *addr).collect();
+    attempted.sort();
+    let mut expected = addrs.to_vec();
+    expected.sort();
+    assert_eq!(attempted, expected);
+
+    server.stop();
+    tokio::task::spawn_blocking(move || handle.join().unwrap()).await.unwrap();
+}
@@ -0,0 +1,160 @@
+use embedded_recruitment_task::{
+    audit::{self, AuditConfig, AuditLog, Entry},
+    client::Client,
+    server::ServerConfig,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+mod common;
+
+/* A fresh path in the temp directory, without the files a previous run may have rotated */
+fn audit_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("audit_test_{}_{}.log", name, std::process::id()));
+    remove_audit_files(&path);
+    path
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
+
+fn remove_audit_files(path: &Path) {
+    let _ = fs::remove_file(path);
+    for index in 1..10 {
+        let _ = fs::remove_file(rotated(path, index));
+    }
+}
+
+fn entry(connection: u64) -> Entry {
+    Entry {
+        timestamp_us: 0,
+        connection,
+        peer: "127.0.0.1:4242".to_string(),
+        message_type: "echo_message".to_string(),
+        outcome: "ok".to_string(),
+        latency_us: 10,
+        request_bytes: 7,
+    }
+}
+
+#[test]
+fn test_requests_are_audited() {
+    let path = audit_path("server");
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |server| {
+        server.enable_audit(AuditConfig::new(&path)).expect("Failed to open the audit log");
+    });
+
+    let mut client = Client::connect(addr).unwrap();
+    assert_eq!(client.echo("audited").unwrap(), "audited");
+    assert!(client.add(i32::MAX, 1).is_err());
+    client.close().unwrap();
+    let mut other = Client::connect(addr).unwrap();
+    assert_eq!(other.add(1, 2).unwrap(), 3);
+    other.close().unwrap();
+
+    // `run` returns once every entry is written
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    let entries = audit::read_audit_log(&path).expect("Failed to read the audit log");
+    let requests: Vec<(&str, &str)> = entries
+        .iter()
+        .map(|entry| (entry.message_type.as_str(), entry.outcome.as_str()))
+        .collect();
+    assert_eq!(requests, [("echo_message", "ok"), ("add_request", "overflow"), ("add_request", "ok")]);
+    assert_eq!(entries[0].connection, entries[1].connection);
+    assert_ne!(entries[1].connection, entries[2].connection);
+    assert!(entries.iter().all(|entry| entry.peer.starts_with("127.0.0.1:")));
+    assert!(entries.iter().all(|entry| entry.request_bytes > 0 && entry.timestamp_us > 0));
+    assert!(entries.windows(2).all(|pair| pair[0].timestamp_us <= pair[1].timestamp_us));
+
+    remove_audit_files(&path);
+}
+
+#[test]
+fn test_audit_log_rotates_by_size_and_keeps_the_newest_files() {
+    let path = audit_path("size");
+    let line = serde_json::to_string(&entry(0)).unwrap().len() as u64 + 1;
+    let log = AuditLog::start(AuditConfig {
+        max_size: Some(line * 2),
+        keep: 2,
+        ..AuditConfig::new(&path)
+    })
+    .expect("Failed to open the audit log");
+
+    // Two entries per file: 1-2, 3-4, 5-6, 7 and the first file is gone
+    for connection in 1..=7 {
+        log.record(entry(connection));
+    }
+    log.flush();
+    assert_eq!(log.dropped(), 0);
+
+    let connections = |path: &Path| -> Vec<u64> {
+        let entries = audit::read_audit_log(path).expect("Failed to read the audit log");
+        entries.iter().map(|entry| entry.connection).collect()
+    };
+    assert_eq!(connections(&path), [7]);
+    assert_eq!(connections(&rotated(&path, 1)), [5, 6]);
+    assert_eq!(connections(&rotated(&path, 2)), [3, 4]);
+    assert!(!rotated(&path, 3).exists(), "Only `keep` rotated files are kept");
+
+    drop(log);
+    remove_audit_files(&path);
+}
+
+#[test]
+fn test_audit_log_rotates_by_age() {
+    let path = audit_path("age");
+    let log = AuditLog::start(AuditConfig {
+        max_size: None,
+        max_age: Some(Duration::from_millis(100)),
+        ..AuditConfig::new(&path)
+    })
+    .expect("Failed to open the audit log");
+
+    log.record(entry(1));
+    log.record(entry(2));
+    log.flush();
+    thread::sleep(Duration::from_millis(150));
+    log.record(entry(3));
+    // Closing writes what is queued
+    log.close();
+
+    let rotated_entries = audit::read_audit_log(rotated(&path, 1)).expect("The old file was not rotated");
+    assert_eq!(rotated_entries, [entry(1), entry(2)]);
+    assert_eq!(audit::read_audit_log(&path).unwrap(), [entry(3)]);
+
+    remove_audit_files(&path);
+}
+
+#[test]
+fn test_audit_log_age_survives_a_restart() {
+    let path = audit_path("restart");
+    let config = AuditConfig {
+        max_size: None,
+        max_age: Some(Duration::from_millis(300)),
+        ..AuditConfig::new(&path)
+    };
+
+    // A first run writes an entry and stops before the file is due
+    let log = AuditLog::start(config.clone()).expect("Failed to open the audit log");
+    log.record(entry(1));
+    log.close();
+
+    // The next run finds the file older than max_age and rotates it on its first entry
+    thread::sleep(Duration::from_millis(400));
+    let log = AuditLog::start(config).expect("Failed to open the audit log");
+    log.record(entry(2));
+    log.close();
+
+    let rotated_entries = audit::read_audit_log(rotated(&path, 1)).expect("The old file was not rotated");
+    assert_eq!(rotated_entries, [entry(1)]);
+    assert_eq!(audit::read_audit_log(&path).unwrap(), [entry(2)]);
+
+    remove_audit_files(&path);
+}
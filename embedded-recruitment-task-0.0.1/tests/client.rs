@@ -1,9 +1,11 @@
-use embedded_recruitment_task::message::{client_message, ServerMessage};
+use embedded_recruitment_task::{
+    client::{self, ClientError},
+    framing,
+    message::{client_message, ClientMessage, ServerMessage},
+};
 // use log::error;
 // use log::info;
 use prost::Message;
-use std::i32;
-use std::io::Read;
 use std::io::Write;
 use std::{
     io,
@@ -30,9 +32,7 @@ impl Client {
     }
 
     // connect the client to the server
-    pub fn connect(&mut self,id:i32) -> io::Result<()> {
-        println!("client-{}:Connecting to {}:{}",id, self.ip, self.port);
-
+    pub fn connect(&mut self,_id:i32) -> Result<(), ClientError> {
         // Resolve the address
         let address = format!("{}:{}", self.ip, self.port);
         let socket_addrs: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
@@ -41,33 +41,34 @@ impl Client {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "Invalid IP or port",
-            ));
+            )
+            .into());
         }
 
-        // Connect to the server with a timeout
-        let stream = TcpStream::connect_timeout(&socket_addrs[0], self.timeout)?;
+        // Connect to whichever address answers first, `localhost` may resolve to ::1 first
+        let stream = client::connect_any(&socket_addrs, Some(self.timeout), client::DEFAULT_ATTEMPT_DELAY)?;
+        // Waiting for a response is bounded too, `receive` fails with a timeout instead of blocking forever
+        stream.set_read_timeout(Some(self.timeout))?;
         self.stream = Some(stream);
-
-        println!("client-{}:Connected to the server!",id);
         Ok(())
     }
 
     // disconnect the client
-    pub fn disconnect(&mut self,id:i32) -> io::Result<()> {
+    pub fn disconnect(&mut self,_id:i32) -> Result<(), ClientError> {
         if let Some(stream) = self.stream.take() {
             stream.shutdown(std::net::Shutdown::Both)?;
         }
-
-        println!("client-{}:Disconnected from the server!",id);
         Ok(())
     }
 
     // generic message to send message to the server
-    pub fn send(&mut self, message: client_message::Message,id:i32) -> io::Result<()> {
+    pub fn send(&mut self, message: client_message::Message,id:i32) -> Result<(), ClientError> {
         if let Some(ref mut stream) = self.stream {
-            // Encode the message to a buffer
-            let mut buffer = Vec::new();
-            message.encode(&mut buffer);
+            // Encode the message to a frame (length prefix + ClientMessage)
+            let buffer = framing::encode_frame(&ClientMessage {
+                message: Some(message),
+                ..Default::default()
+            });
             
             // Print the size of the buffer
             println!("client-{}: Buffer size: {} bytes", id, buffer.len());
@@ -83,59 +84,29 @@ impl Client {
             Err(io::Error::new(
                 io::ErrorKind::NotConnected,
                 "No active connection",
-            ))
+            )
+            .into())
         }
     }
 
-    pub fn receive(&mut self,id:i32) -> io::Result<ServerMessage> {
+    pub fn receive(&mut self,id:i32) -> Result<ServerMessage, ClientError> {
         println!("Function 'receive' started.");
     
         if let Some(ref mut stream) = self.stream {
             println!("Stream is active. Attempting to read from the server...");
     
-            let mut buffer = vec![0u8; 1024]; // Buffer for incoming data
-            println!("CLient a3aaaa:Buffer initialized with size: {}", buffer.len());
-    
-            // // let bytes_read = stream.read(&mut buffer)?;
-            // let bytes_read = match self.stream.read(&mut buffer) {
-            //     Ok(bytes) => bytes,
-            //     Err(e) => {
-            //         println!("client-2: Read error");
-            //         return Err(e); // Propagate or handle the error
-            //     }
-            // };
-            // println!("Read operation completed. Bytes read: {}", bytes_read);
-    
-            // if bytes_read == 0 {
-            //     println!("Receive function: Server disconnected.");
-            //     return Err(io::Error::new(
-            //         io::ErrorKind::ConnectionAborted,
-            //         "Server disconnected",
-            //     ));
-            // }
-
-            // Attempt to read from the stream
-            let bytes_read = match stream.read(&mut buffer) {
-                Ok(0) => {
-                    // If read returns 0, server has disconnected
-                    println!("client-{}: Server disconnected (read returned 0 bytes).",id);
-                    return Err(io::Error::new(
-                        io::ErrorKind::ConnectionAborted,
-                        "Server disconnected",
-                    ));
-                }
-                Ok(bytes) => {
+            // Read one whole frame from the stream
+            let frame = match framing::read_frame(stream, framing::DEFAULT_MAX_FRAME_SIZE) {
+                Ok(frame) => {
                     // Successfully read data
-                    println!("client-{}: Read operation completed. Bytes read: {}",id, bytes);
-                    bytes
+                    println!("client-{}: Read operation completed. Bytes read: {}",id, frame.len());
+                    frame
                 }
                 Err(e) => {
-                    // If read fails, log the error
+                    // Keep the cause: a timeout, the server going away, a bad frame...
+                    let e = ClientError::from(e);
                     println!("client-{}: Read error: {}",id, e);
-                    return Err(io::Error::new(
-                        io::ErrorKind::ConnectionReset,
-                        format!("Failed to read from server: {}", e),
-                    ));
+                    return Err(e);
                 }
             };
     
@@ -143,22 +114,20 @@ impl Client {
     
             // Decode the received message
             println!("Decoding the received message...");
-            let message = ServerMessage::decode(&buffer[..bytes_read]).map_err(|e| {
+            let message = ServerMessage::decode(frame.as_slice()).map_err(|e| {
                 println!("Failed to decode ServerMessage: {}", e);
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Failed to decode ServerMessage: {}", e),
-                )
+                ClientError::Decode(e)
             })?;
     
             println!("Message decoded successfully.");
-            return Ok(message);
+            Ok(message)
         } else {
             println!("Receive function: No active connection.");
-            return Err(io::Error::new(
+            Err(io::Error::new(
                 io::ErrorKind::NotConnected,
                 "No active connection",
-            ));
+            )
+            .into())
         }
     }
 }
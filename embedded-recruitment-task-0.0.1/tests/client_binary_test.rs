@@ -0,0 +1,72 @@
+use embedded_recruitment_task::server::ServerConfig;
+use std::{
+    io::Write,
+    net::SocketAddr,
+    process::{Command, Output, Stdio},
+};
+
+mod common;
+
+fn client(addr: SocketAddr, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_client"))
+        .args(["--addr", &addr.to_string()])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start the client binary");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().expect("Failed to wait for the client binary")
+}
+
+#[test]
+fn test_one_shot_requests() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+
+    let output = client(addr, &["echo", "hello", "world"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), r#"{"echo_message":{"content":"hello world"}}"#);
+
+    let output = client(addr, &["add", "-5", "7"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), r#"{"add_response":{"result":2}}"#);
+
+    // An ErrorResponse is printed too, with its own exit code
+    let output = client(addr, &["add", "2147483647", "1"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#""code":"overflow""#));
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // Nobody answers anymore
+    let output = client(addr, &["--timeout-ms", "300", "echo", "anyone?"], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_repl_with_history() {
+    let (server, addr, handle) = common::start_server(1, ServerConfig::default(), |_| {});
+    let history = std::env::temp_dir().join(format!("client_binary_history_{}", std::process::id()));
+    let _ = std::fs::remove_file(&history);
+
+    let output = client(
+        addr,
+        &["repl", "--history", history.to_str().unwrap()],
+        "echo first\nadd 1 2\n!1\nhistory\nquit\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches(r#"{"echo_message":{"content":"first"}}"#).count(), 2, "!1 did not run the first command again:\n{}", stdout);
+    assert!(stdout.contains(r#"{"add_response":{"result":3}}"#), "{}", stdout);
+    assert!(stdout.contains("   3  echo first"), "The history does not list the expanded command:\n{}", stdout);
+
+    // The history is kept for the next session
+    let saved = std::fs::read_to_string(&history).unwrap();
+    std::fs::remove_file(&history).unwrap();
+    assert_eq!(saved, "echo first\nadd 1 2\necho first\n");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,98 @@
+use embedded_recruitment_task::{
+    client::{Client, ClientConfig, ClientError, ConnectError},
+    message::ErrorCode,
+    server::{ServerConfig, Services},
+};
+use std::{
+    error::Error,
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
+
+mod common;
+
+#[test]
+fn test_echo_and_add() {
+    let (server, _, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+
+    let mut client = Client::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    assert_eq!(client.echo("Hello, library!").unwrap(), "Hello, library!");
+    assert_eq!(client.echo("").unwrap(), "");
+    assert_eq!(client.add(-10, 52).unwrap(), 42);
+
+    // The connection is still usable after an error answered by the server
+    match client.add(i32::MAX, 1) {
+        Err(ClientError::Server { code, .. }) => assert_eq!(code, ErrorCode::Overflow),
+        other => panic!("Expected an overflow error, got {:?}", other),
+    }
+    assert_eq!(client.add(1, 1).unwrap(), 2);
+    client.close().unwrap();
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_errors() {
+    let config = ServerConfig {
+        services: Services { echo: false, add: true },
+        ..ServerConfig::default()
+    };
+    let (server, _, handle) = common::start_server(1, config, |_| {});
+
+    let mut client = Client::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    let error = client.echo("disabled").unwrap_err();
+    assert!(matches!(error, ClientError::Server { code: ErrorCode::ServiceDisabled, .. }), "{:?}", error);
+    assert!(!error.is_connection_error());
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // Nobody listens on a port that was just released
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let error = Client::connect(addr).unwrap_err();
+    assert!(error.is_connection_error());
+    assert!(error.source().is_some(), "The io::Error is the source of the error");
+}
+
+/* An address nobody listens on: a port that was just released */
+fn refused_addr(ip: &str) -> SocketAddr {
+    TcpListener::bind((ip, 0))
+        .map(|listener| listener.local_addr().unwrap())
+        .unwrap_or_else(|_| SocketAddr::new(ip.parse().unwrap(), 1))
+}
+
+#[test]
+fn test_connect_tries_every_address() {
+    let (server, _, handle) = common::start_server(2, ServerConfig::default(), |_| {});
+    let config = ClientConfig {
+        connect_timeout: Some(Duration::from_secs(3)),
+        attempt_delay: Duration::from_millis(100),
+        ..ClientConfig::default()
+    };
+
+    // The server is the last address, after an IPv6 one and another one refusing
+    let addrs = [refused_addr("::1"), refused_addr("127.0.0.1"), server.local_addr().unwrap()];
+    let mut client = Client::with_config(&addrs[..], config.clone()).expect("Failed to connect to the server");
+    assert_eq!(client.peer_addr().unwrap(), server.local_addr().unwrap());
+    assert_eq!(client.add(2, 3).unwrap(), 5);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // When no address accepts, every attempt is reported
+    let addrs = [refused_addr("::1"), refused_addr("127.0.0.1"), refused_addr("127.0.0.1")];
+    let error = match Client::with_config(&addrs[..], config) {
+        Err(ClientError::Io(e)) => e,
+        other => panic!("Expected a connection error, got {:?}", other.map(|_| ())),
+    };
+    let attempts = &error.get_ref().and_then(|e| e.downcast_ref::<ConnectError>()).unwrap().attempts;
+    let mut attempted: Vec<SocketAddr> = attempts.iter().map(|(addr, _)| *addr).collect();
+    attempted.sort();
+    let mut expected = addrs.to_vec();
+    expected.sort();
+    assert_eq!(attempted, expected);
+    for addr in addrs {
+        assert!(error.to_string().contains(&addr.to_string()), "{} is missing from: {}", addr, error);
+    }
+}
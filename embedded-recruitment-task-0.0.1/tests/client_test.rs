@@ -1,38 +1,32 @@
 use embedded_recruitment_task::{
     message::{client_message, server_message, AddRequest, EchoMessage},
-    server::Server
+    server::{Server, ServerConfig},
 };
 use std::{
     sync::Arc,
-    thread::{self, JoinHandle},
+    thread::JoinHandle,
 };
-use once_cell::sync::Lazy;
 mod client;
+mod common;
 
-/* A static server that will be shared by all tests instead of a server for each test*/
-static SERVER: Lazy<Arc<Server>> = Lazy::new(|| {
-    println!("SERVER.clone is called only one time even if any test call it");
-    Arc::new(Server::new("localhost:8080",1).expect("Failed to start server"))
-});
-
-// Helper function to set up the server thread (it will only run once)
-fn setup_server_thread(id:usize) -> JoinHandle<()> {
+/*
+    Each test starts its own server on a free port, so that the tests can run in parallel.
+    Returns the server, its port and the thread running it.
+*/
+fn setup_server_thread(id:usize) -> (Arc<Server>, u32, JoinHandle<()>) {
     println!("setup_server_thread is called from test number {}",id+1);
-    let server = SERVER.clone();
-    thread::spawn(move || {
-        // Server running on a separate thread
-        server.run(id).unwrap();
-    })
+    // Server running on a separate thread
+    let (server, addr, handle) = common::start_server(id, ServerConfig::default(), |_| {});
+    (server, addr.port() as u32, handle)
 }
 
 #[test]
 fn test_client_connection() {
     // Set up the server in a separate thread
-    let server = SERVER.clone();
-    let handle = setup_server_thread(0);
+    let (server, port, handle) = setup_server_thread(0);
 
     // Create and connect the client
-    let mut client = client::Client::new("localhost", 8080, 1000);
+    let mut client = client::Client::new("localhost", port, 1000);
     assert!(client.connect(1).is_ok(), "Failed to connect to the server");
 
     // Disconnect the client
@@ -42,7 +36,7 @@ fn test_client_connection() {
     );
     
     // Stop the server and wait for thread to finish
-    server.stop(0);
+    server.stop();
     assert!(
         handle.join().is_ok(),
         "Server thread panicked or failed to join"
@@ -52,16 +46,16 @@ fn test_client_connection() {
 #[test]
 fn test_client_echo_message() {
     // Set up the server in a separate thread
-    let server = SERVER.clone();
-    let handle = setup_server_thread(1);
+    let (server, port, handle) = setup_server_thread(1);
 
     // Create and connect the client
-    let mut client = client::Client::new("localhost", 8080, 2000);
+    let mut client = client::Client::new("localhost", port, 2000);
     assert!(client.connect(2).is_ok(), "Failed to connect to the server");
 
     // Prepare the message
-    let mut echo_message = EchoMessage::default();
-    echo_message.content = "Hello, World!".to_string();
+    let echo_message = EchoMessage {
+        content: "Hello, World!".to_string(),
+    };
     let message = client_message::Message::EchoMessage(echo_message.clone());
 
     // Lock the mutex before calling send
@@ -92,7 +86,7 @@ fn test_client_echo_message() {
     );
     
     // Stop the server and wait for thread to finish
-    server.stop(1);
+    server.stop();
     assert!(
         handle.join().is_ok(),
         "Server thread panicked or failed to join"
@@ -102,11 +96,10 @@ fn test_client_echo_message() {
 #[test]
 fn test_multiple_echo_messages() {
     // Set up the server in a separate thread
-    let server = SERVER.clone();
-    let handle = setup_server_thread(2);
+    let (server, port, handle) = setup_server_thread(2);
 
     // Create and connect the client
-    let mut client = client::Client::new("localhost", 8080, 1000);
+    let mut client = client::Client::new("localhost", port, 1000);
     assert!(client.connect(3).is_ok(), "Failed to connect to the server");
 
     // Prepare multiple messages
@@ -118,8 +111,9 @@ fn test_multiple_echo_messages() {
 
     // Send and receive multiple messages
     for message_content in messages {
-        let mut echo_message = EchoMessage::default();
-        echo_message.content = message_content.clone();
+        let echo_message = EchoMessage {
+            content: message_content.clone(),
+        };
         let message = client_message::Message::EchoMessage(echo_message);
 
         assert!(client.send(message,3).is_ok(), "Failed to send message");
@@ -148,7 +142,7 @@ fn test_multiple_echo_messages() {
     );
 
     // Stop the server and wait for thread to finish
-    server.stop(2);
+    server.stop();
     assert!(
         handle.join().is_ok(),
         "Server thread panicked or failed to join"
@@ -158,14 +152,13 @@ fn test_multiple_echo_messages() {
 #[test]
 fn test_multiple_clients() {
     // Set up the server in a separate thread
-    let handle = setup_server_thread(3);
-    let server = SERVER.clone();
+    let (server, port, handle) = setup_server_thread(3);
 
     // Create and connect multiple clients
-    let mut clients = vec![
-        client::Client::new("localhost", 8080, 1000),
-        client::Client::new("localhost", 8080, 1000),
-        client::Client::new("localhost", 8080, 1000),
+    let mut clients = [
+        client::Client::new("localhost", port, 1000),
+        client::Client::new("localhost", port, 1000),
+        client::Client::new("localhost", port, 1000),
     ];
 
     for client in clients.iter_mut() {
@@ -181,8 +174,9 @@ fn test_multiple_clients() {
 
     // Send and receive multiple messages for each client
     for message_content in messages {
-        let mut echo_message = EchoMessage::default();
-        echo_message.content = message_content.clone();
+        let echo_message = EchoMessage {
+            content: message_content.clone(),
+        };
         let message = client_message::Message::EchoMessage(echo_message.clone());
 
         for client in clients.iter_mut() {
@@ -219,7 +213,7 @@ fn test_multiple_clients() {
     }
 
     // Stop the server and wait for thread to finish
-    server.stop(3);
+    server.stop();
     assert!(
         handle.join().is_ok(),
         "Server thread panicked or failed to join"
@@ -228,18 +222,15 @@ fn test_multiple_clients() {
 #[test]
 fn test_client_add_request() {
     // Set up the server in a separate thread
-    let handle = setup_server_thread(4);
-    let server = SERVER.clone();
+    let (server, port, handle) = setup_server_thread(4);
 
     // Create and connect the client
-    let mut client = client::Client::new("localhost", 8080, 1000);
+    let mut client = client::Client::new("localhost", port, 1000);
     assert!(client.connect(5).is_ok(), "Failed to connect to the server");
 
     // Prepare the message
-    let mut add_request = AddRequest::default();
-    add_request.a = 10;
-    add_request.b = 20;
-    let message = client_message::Message::AddRequest(add_request.clone());
+    let add_request = AddRequest { a: 10, b: 20 };
+    let message = client_message::Message::AddRequest(add_request);
 
     // Send the message to the server
     assert!(client.send(message, 5).is_ok(), "Failed to send message");
@@ -264,7 +255,7 @@ fn test_client_add_request() {
     assert!(client.disconnect(5).is_ok(), "Failed to disconnect from the server");
     
     // Stop the server and wait for the thread to finish
-    server.stop(4);
+    server.stop();
     assert!(
         handle.join().is_ok(),
         "Server thread panicked or failed to join"
@@ -0,0 +1,38 @@
+/*
+    The server fixture shared by the integration tests. Every test binary picks what it needs,
+    the rest is unused there.
+*/
+#![allow(dead_code)]
+
+use embedded_recruitment_task::server::{Server, ServerConfig};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+/*
+    Starts a server with `config` on a free port of 127.0.0.1, `configure` may enable its other
+    listeners (see `Server::enable_http` and the like) before it runs. Returns the server with
+    its address and the thread running it.
+*/
+pub fn start_server(
+    id: usize,
+    config: ServerConfig,
+    configure: impl FnOnce(&mut Server),
+) -> (Arc<Server>, SocketAddr, JoinHandle<()>) {
+    let mut server = Server::with_config("127.0.0.1:0", config).expect("Failed to start server");
+    configure(&mut server);
+    run_server(server, id)
+}
+
+/* Runs `server` on a thread of its own, returns it with its address and that thread */
+pub fn run_server(server: Server, id: usize) -> (Arc<Server>, SocketAddr, JoinHandle<()>) {
+    let addr = server.local_addr().expect("Failed to get the server address");
+    let server = Arc::new(server);
+    let handle = {
+        let server = Arc::clone(&server);
+        thread::spawn(move || server.run(id).unwrap())
+    };
+    (server, addr, handle)
+}
@@ -0,0 +1,77 @@
+use embedded_recruitment_task::config::{Config, ConfigError};
+use std::{fs, time::Duration};
+
+#[test]
+fn test_full_file_is_loaded() {
+    let path = std::env::temp_dir().join(format!("config_test_{}.toml", std::process::id()));
+    fs::write(
+        &path,
+        r#"
+            [listeners]
+            tcp = "127.0.0.1:9000"
+            http = "127.0.0.1:9001"
+            metrics = "127.0.0.1:9100"
+
+            [limits]
+            workers = 4
+            max_connections = 10
+
+            [timeouts]
+            read_ms = 1500
+            drain_ms = 500
+
+            [logging]
+            level = "debug"
+
+            [services]
+            add = false
+        "#,
+    )
+    .unwrap();
+    let config = Config::load(&path);
+    fs::remove_file(&path).unwrap();
+    let config = config.expect("The configuration should be valid");
+
+    assert_eq!(config.listeners.tcp, "127.0.0.1:9000");
+    assert_eq!(config.listeners.http.as_deref(), Some("127.0.0.1:9001"));
+    assert_eq!(config.listeners.metrics.as_deref(), Some("127.0.0.1:9100"));
+    assert_eq!(config.listeners.websocket, None, "Missing keys keep their default");
+
+    let server_config = config.server_config();
+    assert_eq!(server_config.workers, 4);
+    assert_eq!(server_config.max_connections, Some(10));
+    assert_eq!(server_config.read_timeout, Some(Duration::from_millis(1500)));
+    assert_eq!(server_config.write_timeout, None);
+    assert_eq!(server_config.drain_timeout, Some(Duration::from_millis(500)));
+    assert!(server_config.services.echo && !server_config.services.add);
+    assert_eq!(config.log_level(), log::LevelFilter::Debug);
+
+    // The printed configuration reads back to the same settings
+    assert_eq!(Config::parse(&config.to_toml(), "printed").unwrap(), config);
+}
+
+#[test]
+fn test_errors_name_the_offending_key() {
+    let cases = [
+        ("[limits]\nmax_conections = 10\n", "max_conections"),
+        ("[timeouts]\nread_ms = \"soon\"\n", "read_ms"),
+        ("[timeouts]\nread_ms = 0\n", "timeouts.read_ms"),
+        ("[listeners]\ntcp = \"localhost\"\n", "listeners.tcp"),
+        ("[logging]\nlevel = \"loud\"\n", "logging.level"),
+        // there is no TLS support, so no section to configure it
+        ("[tls]\nenabled = true\n", "unknown field `tls`"),
+        ("[audit]\npath = \"audit.log\"\nmax_size_bytes = 0\n", "audit.max_size_bytes"),
+    ];
+    for (text, key) in cases {
+        let error = Config::parse(text, "test.toml").expect_err(text).to_string();
+        assert!(error.contains(key), "`{}` does not point at `{}`", error, key);
+    }
+}
+
+#[test]
+fn test_missing_file_is_reported() {
+    match Config::load("/nonexistent/server.toml") {
+        Err(ConfigError::Io { path, .. }) => assert_eq!(path, "/nonexistent/server.toml"),
+        other => panic!("Expected an Io error, got {:?}", other),
+    }
+}
@@ -0,0 +1,103 @@
+use embedded_recruitment_task::{
+    client::{Client, ClientError},
+    framing,
+    message::{client_message, server_message, ClientMessage, EchoMessage, ErrorCode, ServerMessage},
+    server::ServerConfig,
+};
+use prost::Message;
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+mod common;
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+fn echo(content: &str, deadline_unix_ms: u64) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        deadline_unix_ms,
+    }
+}
+
+fn exchange(stream: &mut TcpStream, request: &ClientMessage) -> ServerMessage {
+    stream.write_all(&framing::encode_frame(request)).unwrap();
+    let frame = framing::read_frame(stream, framing::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    ServerMessage::decode(frame.as_slice()).unwrap()
+}
+
+#[test]
+fn test_server_skips_expired_requests() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+
+    // A request whose deadline passed on its way (or while it waited for the server) is not served
+    let mut waiting = TcpStream::connect(addr).unwrap();
+    let deadline = unix_ms(SystemTime::now() - Duration::from_millis(100));
+    match exchange(&mut waiting, &echo("too late", deadline)).message {
+        Some(server_message::Message::ErrorResponse(error)) => {
+            assert_eq!(error.code, ErrorCode::DeadlineExceeded as i32, "{}", error.message)
+        }
+        other => panic!("Expected DEADLINE_EXCEEDED, got {:?}", other),
+    }
+
+    // A deadline in the future, or none at all, is served as usual
+    let deadline = unix_ms(SystemTime::now() + Duration::from_secs(5));
+    let response = exchange(&mut waiting, &echo("in time", deadline));
+    assert_eq!(
+        response.message,
+        Some(server_message::Message::EchoMessage(EchoMessage {
+            content: "in time".to_string()
+        }))
+    );
+    let response = exchange(&mut waiting, &echo("no deadline", 0));
+    assert!(matches!(response.message, Some(server_message::Message::EchoMessage(_))));
+    drop(waiting);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_client_sends_and_enforces_the_deadline() {
+    // A server that reads the request and never answers
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let silent = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let frame = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).unwrap();
+        let request = ClientMessage::decode(frame.as_slice()).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        request
+    });
+
+    let mut client = Client::connect(addr).unwrap();
+    let before = SystemTime::now();
+    let request = client_message::Message::EchoMessage(EchoMessage {
+        content: "anyone?".to_string(),
+    });
+    let error = client.call_within(request, Duration::from_millis(150)).unwrap_err();
+    let elapsed = before.elapsed().unwrap();
+    assert!(matches!(error, ClientError::Timeout(_)), "{:?}", error);
+    assert!(
+        elapsed >= Duration::from_millis(150) && elapsed < Duration::from_millis(450),
+        "Gave up after {:?}",
+        elapsed
+    );
+
+    // The deadline the server received is the one the client enforced
+    let request = silent.join().unwrap();
+    let sent_after = unix_ms(before + Duration::from_millis(150));
+    assert!(
+        request.deadline_unix_ms >= sent_after && request.deadline_unix_ms < sent_after + 100,
+        "deadline {} expected around {}",
+        request.deadline_unix_ms,
+        sent_after
+    );
+}
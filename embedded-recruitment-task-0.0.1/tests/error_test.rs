@@ -0,0 +1,154 @@
+use embedded_recruitment_task::{
+    client::{self, ClientConfig, ClientError},
+    framing::{self, FrameError},
+    message::{server_message, ErrorCode, ServerMessage},
+    server::{self, ServerConfig, ServerError},
+    stats::{ServerStats, TimeoutKind},
+};
+use prost::Message;
+use std::{
+    error::Error,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/* A connected pair of sockets: the client side and the server side */
+fn socket_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    (client, server)
+}
+
+/* Runs the handler until it gives up on the connection */
+fn handle_until_error(handler: &mut server::Client) -> ServerError {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if let Err(e) = handler.handle(0) {
+            return e;
+        }
+    }
+    panic!("The handler never failed");
+}
+
+/* A fake server answering the first request of one connection with `answer` */
+fn answer_with(answer: Vec<u8>) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE);
+        stream.write_all(&answer).unwrap();
+        /* keep the connection open until the client is done with it */
+        let _ = stream.read(&mut [0u8; 1]);
+    });
+    (addr, handle)
+}
+
+/* A fake server writing `answer` after the first request of one connection, then closing it */
+fn answer_and_close(answer: Vec<u8>) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE);
+        stream.write_all(&answer).unwrap();
+    });
+    (addr, handle)
+}
+
+#[test]
+fn test_server_errors() {
+    // A frame announcing more than max_frame_size is a framing error
+    let (mut peer, stream) = socket_pair();
+    let config = ServerConfig {
+        max_frame_size: 16,
+        ..ServerConfig::default()
+    };
+    let mut handler = server::Client::with_config(stream, config, Arc::new(ServerStats::default()));
+    peer.write_all(&[0x80, 0x01]).unwrap();
+    let error = handle_until_error(&mut handler);
+    assert!(
+        matches!(error, ServerError::Frame(FrameError::TooLarge { size: 128, max: 16 })),
+        "{:?}",
+        error
+    );
+    assert!(error.source().unwrap().is::<FrameError>());
+
+    // A client that sends nothing runs into the read timeout
+    let (_peer, stream) = socket_pair();
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(150)),
+        ..ServerConfig::default()
+    };
+    let mut handler = server::Client::with_config(stream, config, Arc::new(ServerStats::default()));
+    match handle_until_error(&mut handler) {
+        ServerError::Timeout { kind, limit } => {
+            assert_eq!(kind, TimeoutKind::Read);
+            assert_eq!(limit, Duration::from_millis(150));
+        }
+        other => panic!("Expected a read timeout, got {:?}", other),
+    }
+
+    // A whole frame that is not a ClientMessage is answered, then the connection is closed
+    let (mut peer, stream) = socket_pair();
+    let mut handler = server::Client::with_config(stream, ServerConfig::default(), Arc::new(ServerStats::default()));
+    peer.write_all(&[0x02, 0xff, 0xff]).unwrap();
+    let error = handle_until_error(&mut handler);
+    assert!(matches!(error, ServerError::Decode(_)), "{:?}", error);
+    assert!(error.source().unwrap().is::<prost::DecodeError>());
+    let answer = framing::read_frame(&mut peer, framing::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let answer = ServerMessage::decode(answer.as_slice()).unwrap();
+    match answer.message {
+        Some(server_message::Message::ErrorResponse(response)) => {
+            assert_eq!(response.code, ErrorCode::InvalidRequest as i32)
+        }
+        other => panic!("Expected an error response, got {:?}", other),
+    }
+    assert_eq!(peer.read(&mut [0u8; 1]).unwrap(), 0);
+}
+
+#[test]
+fn test_client_errors() {
+    let config = ClientConfig {
+        read_timeout: Some(Duration::from_millis(200)),
+        ..ClientConfig::default()
+    };
+
+    // The server closes the connection without answering, between two frames it is shutting down
+    let (addr, closing) = answer_and_close(Vec::new());
+    let error = client::Client::with_config(addr, config.clone()).unwrap().echo("hi").unwrap_err();
+    assert!(matches!(error, ClientError::Shutdown(_)), "{:?}", error);
+    assert!(error.is_connection_error());
+    closing.join().unwrap();
+
+    // The server closes the connection in the middle of a response
+    let (addr, closing) = answer_and_close(vec![0x05, 0x0a]);
+    let error = client::Client::with_config(addr, config.clone()).unwrap().echo("hi").unwrap_err();
+    assert!(matches!(error, ClientError::Closed(_)), "{:?}", error);
+    closing.join().unwrap();
+
+    // The server never answers
+    let (addr, handle) = answer_with(Vec::new());
+    let error = client::Client::with_config(addr, config.clone()).unwrap().echo("hi").unwrap_err();
+    assert!(matches!(error, ClientError::Timeout(_)), "{:?}", error);
+    assert!(error.source().is_some());
+    handle.join().unwrap();
+
+    // The response announces a frame larger than max_frame_size
+    let (addr, handle) = answer_with(vec![0xff, 0xff, 0xff, 0x7f]);
+    let error = client::Client::with_config(addr, config.clone()).unwrap().echo("hi").unwrap_err();
+    assert!(matches!(error, ClientError::Frame(FrameError::TooLarge { .. })), "{:?}", error);
+    assert!(error.source().unwrap().is::<FrameError>());
+    handle.join().unwrap();
+
+    // The response is a whole frame that is not a ServerMessage
+    let (addr, handle) = answer_with(vec![0x02, 0xff, 0xff]);
+    let error = client::Client::with_config(addr, config).unwrap().echo("hi").unwrap_err();
+    assert!(matches!(error, ClientError::Decode(_)), "{:?}", error);
+    assert!(error.is_connection_error());
+    handle.join().unwrap();
+}
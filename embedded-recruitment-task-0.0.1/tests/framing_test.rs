@@ -0,0 +1,119 @@
+use embedded_recruitment_task::{
+    framing,
+    message::{client_message, server_message, AddRequest, ClientMessage, EchoMessage, ErrorCode, ServerMessage},
+    server::{Server, ServerConfig},
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+mod common;
+
+/* Starts a server applying `config` on a free port and connects one raw TCP stream to it */
+fn setup_server(id: usize, config: ServerConfig) -> (Arc<Server>, JoinHandle<()>, TcpStream) {
+    let (server, addr, handle) = common::start_server(id, config, |_| {});
+    let stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    (server, handle, stream)
+}
+
+fn echo(content: &str) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+fn receive(stream: &mut TcpStream) -> ServerMessage {
+    let frame = framing::read_frame(stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    ServerMessage::decode(frame.as_slice()).expect("Failed to decode ServerMessage")
+}
+
+#[test]
+fn test_frame_split_across_writes() {
+    let (server, handle, mut stream) = setup_server(0, ServerConfig::default());
+
+    // Send the frame a few bytes at a time, slower than the server polls the socket
+    let frame = framing::encode_frame(&echo("Hello, slow network!"));
+    for chunk in frame.chunks(3) {
+        stream.write_all(chunk).expect("Failed to send chunk");
+        stream.flush().unwrap();
+        thread::sleep(Duration::from_millis(150));
+    }
+
+    match receive(&mut stream).message {
+        Some(server_message::Message::EchoMessage(echo)) => {
+            assert_eq!(echo.content, "Hello, slow network!", "Echoed message content does not match");
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_pipelined_frames_in_one_write() {
+    let (server, handle, mut stream) = setup_server(1, ServerConfig::default());
+
+    // Three requests in a single write are answered in order
+    let mut bytes = framing::encode_frame(&echo("one"));
+    bytes.extend(framing::encode_frame(&ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a: 1, b: 2 })),
+        ..Default::default()
+    }));
+    bytes.extend(framing::encode_frame(&echo("three")));
+    stream.write_all(&bytes).expect("Failed to send messages");
+
+    match receive(&mut stream).message {
+        Some(server_message::Message::EchoMessage(echo)) => assert_eq!(echo.content, "one"),
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+    match receive(&mut stream).message {
+        Some(server_message::Message::AddResponse(add_response)) => assert_eq!(add_response.result, 3),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+    match receive(&mut stream).message {
+        Some(server_message::Message::EchoMessage(echo)) => assert_eq!(echo.content, "three"),
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_oversized_frame_is_rejected() {
+    let config = ServerConfig {
+        max_frame_size: 16,
+        ..ServerConfig::default()
+    };
+    let (server, handle, mut stream) = setup_server(2, config);
+
+    stream
+        .write_all(&framing::encode_frame(&echo("this message is longer than sixteen bytes")))
+        .expect("Failed to send message");
+
+    match receive(&mut stream).message {
+        Some(server_message::Message::ErrorResponse(error)) => {
+            assert_eq!(error.code, ErrorCode::FrameTooLarge as i32, "Unexpected error code");
+        }
+        _ => panic!("Expected ErrorResponse, but received a different message"),
+    }
+
+    // The connection is closed afterwards
+    let mut buffer = [0u8; 16];
+    assert_eq!(stream.read(&mut buffer).unwrap_or(0), 0, "Expected the connection to be closed");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,133 @@
+use embedded_recruitment_task::{
+    client::Client,
+    framing,
+    message::{client_message, ClientMessage, EchoMessage},
+    server::{Server, ServerConfig},
+};
+use serde_json::Value;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+fn wait_until_ready(server: &Server) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !server.health().ready {
+        assert!(Instant::now() < deadline, "The server never became ready: {:?}", server.health());
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/* Sends a bare HTTP/1.1 GET and returns the status code and the JSON body */
+fn probe(addr: SocketAddr, path: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the health endpoint");
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("Failed to read HTTP response");
+    let (head, body) = response.split_once("\r\n\r\n").expect("Malformed HTTP response");
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .expect("Missing HTTP status code");
+    (status, serde_json::from_str(body).unwrap_or(Value::Null))
+}
+
+#[test]
+fn test_health_check_reports_readiness() {
+    let config = ServerConfig {
+        max_connections: Some(2),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("127.0.0.1:0", config).expect("Failed to start server");
+    let addr = server.local_addr().unwrap();
+
+    // Bound but not accepting yet
+    let health = server.health();
+    assert!(health.live && !health.ready && !health.listening, "{:?}", health);
+
+    let (server, _, handle) = common::run_server(server, 0);
+    wait_until_ready(&server);
+
+    let mut first = Client::connect(addr).unwrap();
+    let health = first.health().unwrap();
+    assert!(health.live && health.ready && health.listening, "{:?}", health);
+    assert_eq!(health.active_connections, 1);
+
+    // The second client takes the last connection slot: the next one would be turned away
+    let mut second = Client::connect(addr).unwrap();
+    let health = second.health().unwrap();
+    assert!(health.live && health.saturated && !health.ready, "{:?}", health);
+
+    second.close().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while first.health().unwrap().saturated {
+        assert!(Instant::now() < deadline, "The server stayed saturated");
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(first.health().unwrap().ready);
+    first.close().unwrap();
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    let health = server.health();
+    assert!(!health.live && !health.ready && health.draining, "{:?}", health);
+}
+
+#[test]
+fn test_probe_endpoint_turns_unready_while_draining() {
+    let mut server = Server::new("127.0.0.1:0").expect("Failed to start server");
+    let health_addr = server.enable_health("127.0.0.1:0").expect("Failed to bind the health listener");
+    assert_eq!(server.health_addr(), Some(health_addr));
+    let addr = server.local_addr().unwrap();
+    let (server, _, handle) = common::run_server(server, 1);
+    wait_until_ready(&server);
+
+    let (status, body) = probe(health_addr, "/readyz");
+    assert_eq!(status, 200);
+    assert_eq!(body["ready"], true);
+    assert_eq!(probe(health_addr, "/livez").0, 200);
+    assert_eq!(probe(health_addr, "/other").0, 404);
+
+    // A query string does not change the probe
+    assert_eq!(probe(health_addr, "/livez?x=1").0, 200);
+    let (status, body) = probe(health_addr, "/readyz?probe=k8s");
+    assert_eq!(status, 200);
+    assert_eq!(body["ready"], true);
+
+    // A client in the middle of a request keeps the server draining after the stop
+    let request = framing::encode_frame(&ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "in flight".to_string(),
+        })),
+        ..Default::default()
+    });
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(&request[..1]).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.stats().bytes_received == 0 {
+        assert!(Instant::now() < deadline, "The partial request never arrived");
+        thread::sleep(Duration::from_millis(10));
+    }
+    server.stop();
+
+    let (status, body) = probe(health_addr, "/readyz");
+    assert_eq!(status, 503);
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["draining"], true);
+    let (status, body) = probe(health_addr, "/livez");
+    assert_eq!(status, 200, "Still live while draining");
+    assert_eq!(body["live"], true);
+
+    // The request in flight is completed, then the server stops for good
+    stream.write_all(&request[1..]).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    drop(stream);
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    assert!(!server.health().live);
+}
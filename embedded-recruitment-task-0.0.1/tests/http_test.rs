@@ -0,0 +1,226 @@
+use embedded_recruitment_task::server::{Server, ServerConfig};
+use serde_json::{json, Value};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+mod common;
+
+/* Starts a server with only the HTTP gateway of interest here, returns it with the gateway address */
+fn setup_server(id: usize, config: ServerConfig) -> (Arc<Server>, SocketAddr, JoinHandle<()>) {
+    let (server, _, handle) = common::start_server(id, config, |server| {
+        server.enable_http("127.0.0.1:0").expect("Failed to bind HTTP listener");
+    });
+    let http_addr = server.http_addr().unwrap();
+    (server, http_addr, handle)
+}
+
+/*
+    Body length of the slow requests, over 1024 bytes: tiny_http reads the smaller bodies itself
+    before handing the request over
+*/
+const SLOW_BODY_LENGTH: usize = 2000;
+
+/* Starts a POST /echo that announces a SLOW_BODY_LENGTH body and only sends the start of it */
+fn start_slow_request(addr: SocketAddr) -> TcpStream {
+    let mut slow = TcpStream::connect(addr).expect("Failed to connect to the HTTP gateway");
+    write!(
+        slow,
+        "POST /echo HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{{\"content\":",
+        addr, SLOW_BODY_LENGTH
+    )
+    .expect("Failed to send HTTP request");
+    slow.flush().unwrap();
+    slow
+}
+
+/* Sends the rest of the body of a slow request and returns the response */
+fn finish_slow_request(mut slow: TcpStream) -> String {
+    let rest = "\"slow\"}";
+    let padding = " ".repeat(SLOW_BODY_LENGTH - "{\"content\":".len() - rest.len());
+    write!(slow, "{}{}", rest, padding).expect("Failed to send the rest of the body");
+    let mut response = String::new();
+    slow.read_to_string(&mut response).expect("Failed to read HTTP response");
+    response
+}
+
+/* Sends a bare HTTP/1.1 request and returns the status code with the JSON body */
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the HTTP gateway");
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    )
+    .expect("Failed to send HTTP request");
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .expect("Failed to read HTTP response");
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("Malformed HTTP response");
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .expect("Missing HTTP status code");
+    (status, serde_json::from_str(body).expect("Response body is not JSON"))
+}
+
+#[test]
+fn test_http_echo_and_add() {
+    let (server, addr, handle) = setup_server(0, ServerConfig::default());
+
+    let (status, body) = request(addr, "POST", "/echo", r#"{"content":"Hello, HTTP!"}"#);
+    assert_eq!(status, 200, "Unexpected status for /echo");
+    assert_eq!(body, json!({"echo_message": {"content": "Hello, HTTP!"}}));
+
+    let (status, body) = request(addr, "POST", "/add", r#"{"a":10,"b":20}"#);
+    assert_eq!(status, 200, "Unexpected status for /add");
+    assert_eq!(body, json!({"add_response": {"result": 30}}));
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_http_errors() {
+    let (server, addr, handle) = setup_server(1, ServerConfig::default());
+
+    // Overflowing additions are reported by the protocol and mapped to 422
+    let (status, body) = request(addr, "POST", "/add", r#"{"a":2147483647,"b":1}"#);
+    assert_eq!(status, 422, "Unexpected status for an overflowing add");
+    assert_eq!(body["error_response"]["code"], "overflow");
+
+    // Bodies that are not the expected JSON are bad requests
+    let (status, body) = request(addr, "POST", "/add", r#"{"a":"ten"}"#);
+    assert_eq!(status, 400, "Unexpected status for an invalid body");
+    assert_eq!(body["error_response"]["code"], "invalid_request");
+
+    let (status, _) = request(addr, "GET", "/echo", "");
+    assert_eq!(status, 405, "Unexpected status for a GET");
+
+    let (status, _) = request(addr, "POST", "/subtract", "{}");
+    assert_eq!(status, 404, "Unexpected status for an unknown endpoint");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_http_body_over_max_frame_size() {
+    let config = ServerConfig {
+        max_frame_size: 64,
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = setup_server(4, config);
+
+    // A body up to max_frame_size is served, a larger one is refused like a TCP frame would be
+    let (status, _) = request(addr, "POST", "/echo", &format!(r#"{{"content":"{}"}}"#, "x".repeat(40)));
+    assert_eq!(status, 200, "Unexpected status for a body within the limit");
+    let (status, body) = request(addr, "POST", "/echo", &format!(r#"{{"content":"{}"}}"#, "x".repeat(100)));
+    assert_eq!(status, 413, "Unexpected status for a body over the limit");
+    assert_eq!(body["error_response"]["code"], "frame_too_large");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_http_query_string_is_ignored() {
+    let (server, addr, handle) = setup_server(2, ServerConfig::default());
+
+    let (status, body) = request(addr, "POST", "/echo?x=1", r#"{"content":"query"}"#);
+    assert_eq!(status, 200, "Unexpected status for /echo with a query string");
+    assert_eq!(body, json!({"echo_message": {"content": "query"}}));
+
+    let (status, _) = request(addr, "GET", "/stats?format=json", "");
+    assert_eq!(status, 200, "Unexpected status for /stats with a query string");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_http_slow_client_does_not_block_others() {
+    let (server, addr, handle) = setup_server(3, ServerConfig::default());
+
+    // A client that announces a body and only sends half of it
+    let slow = start_slow_request(addr);
+    thread::sleep(Duration::from_millis(200));
+
+    // Other clients are still answered meanwhile
+    let started = Instant::now();
+    let (status, body) = request(addr, "POST", "/add", r#"{"a":1,"b":2}"#);
+    assert_eq!(status, 200, "Unexpected status for /add");
+    assert_eq!(body, json!({"add_response": {"result": 3}}));
+    assert!(started.elapsed() < Duration::from_secs(2), "The gateway was blocked by the slow client");
+
+    // The slow client finishes its request and gets its answer too
+    let response = finish_slow_request(slow);
+    assert!(response.starts_with("HTTP/1.1 200"), "Unexpected response: {}", response);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_http_requests_count_against_max_connections() {
+    let config = ServerConfig {
+        max_connections: Some(1),
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = setup_server(5, config);
+
+    // The slow request holds the only slot, the next one is turned away
+    let slow = start_slow_request(addr);
+    thread::sleep(Duration::from_millis(200));
+    let (status, _) = request(addr, "POST", "/add", r#"{"a":1,"b":2}"#);
+    assert_eq!(status, 503, "Unexpected status over max_connections");
+    assert_eq!(server.stats().rejected_connections, 1);
+
+    // Once it is answered the slot is free again
+    let response = finish_slow_request(slow);
+    assert!(response.starts_with("HTTP/1.1 200"), "Unexpected response: {}", response);
+    thread::sleep(Duration::from_millis(100));
+    let (status, _) = request(addr, "POST", "/add", r#"{"a":1,"b":2}"#);
+    assert_eq!(status, 200, "Unexpected status once the slot is free");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_http_stalled_body() {
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(300)),
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = setup_server(6, config);
+
+    // A body that stops coming is given up after read_timeout
+    let _stalled = start_slow_request(addr);
+    let started = Instant::now();
+    while server.stats().read_timeouts == 0 {
+        assert!(started.elapsed() < Duration::from_secs(5), "The stalled body never timed out");
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // Even without a drain timeout, a stalled client does not keep the server from stopping
+    let _stalled = start_slow_request(addr);
+    thread::sleep(Duration::from_millis(100));
+    let stopping = Instant::now();
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    assert!(stopping.elapsed() < Duration::from_secs(3), "The stalled client held up the server");
+}
@@ -0,0 +1,32 @@
+use embedded_recruitment_task::server::ServerConfig;
+use serde_json::Value;
+use std::process::Command;
+
+mod common;
+
+#[test]
+fn test_fixed_number_of_requests_as_json() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+
+    let output = Command::new(env!("CARGO_BIN_EXE_loadgen"))
+        .args(["--addr", &addr.to_string()])
+        .args(["--connections", "3", "--requests", "100", "--echo-percent", "25", "--json"])
+        .output()
+        .expect("Failed to start the loadgen binary");
+    assert!(output.status.success(), "loadgen failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let report: Value = serde_json::from_slice(&output.stdout).expect("The report is not JSON");
+    assert_eq!(report["requests"], 100);
+    assert_eq!(report["echo_requests"], 25, "The mix does not follow --echo-percent");
+    assert_eq!(report["add_requests"], 75);
+    assert_eq!(report["errors"], serde_json::json!({"io": 0, "error_responses": 0, "mismatches": 0}));
+
+    let latency = &report["latency_us"];
+    let p50 = latency["p50"].as_u64().unwrap();
+    let p99 = latency["p99"].as_u64().unwrap();
+    let p999 = latency["p999"].as_u64().unwrap();
+    assert!(p50 > 0 && p50 <= p99 && p99 <= p999, "Percentiles out of order: {}", latency);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,160 @@
+use embedded_recruitment_task::{
+    client::Client,
+    logging::{self, Connection},
+    server::ServerConfig,
+};
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+/* What the test logger kept of a record */
+#[derive(Debug, Clone)]
+struct Captured {
+    level: Level,
+    message: String,
+    fields: BTreeMap<String, String>,
+    connection: Option<Connection>,
+}
+
+struct TestLogger {
+    records: Mutex<Vec<Captured>>,
+}
+
+struct FieldCollector(BTreeMap<String, String>);
+
+impl<'kvs> VisitSource<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
+
+impl Log for TestLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut fields = FieldCollector(BTreeMap::new());
+        record.key_values().visit(&mut fields).unwrap();
+        self.records.lock().unwrap().push(Captured {
+            level: record.level(),
+            message: record.args().to_string(),
+            fields: fields.0,
+            /* called on the thread that logged, so this is the connection it was serving */
+            connection: logging::current_connection(),
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+fn logger() -> &'static TestLogger {
+    static LOGGER: OnceLock<&'static TestLogger> = OnceLock::new();
+    LOGGER.get_or_init(|| {
+        let logger = Box::leak(Box::new(TestLogger {
+            records: Mutex::new(Vec::new()),
+        }));
+        log::set_logger(logger).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+        logger
+    })
+}
+
+#[test]
+fn test_connection_span() {
+    assert_eq!(logging::current_connection(), None);
+    let outer = logging::connection_span("10.0.0.1:1000");
+    {
+        let inner = logging::connection_span("10.0.0.2:2000");
+        assert_ne!(inner.id(), outer.id());
+        assert_eq!(
+            logging::current_connection(),
+            Some(Connection {
+                id: inner.id(),
+                peer: "10.0.0.2:2000".to_string()
+            })
+        );
+    }
+    // Dropping a span goes back to the one it was opened in
+    assert_eq!(logging::current_connection().map(|connection| connection.id), Some(outer.id()));
+    drop(outer);
+    assert_eq!(logging::current_connection(), None);
+
+    // Another thread serves another connection
+    let _span = logging::connection_span("10.0.0.3:3000");
+    thread::spawn(|| assert_eq!(logging::current_connection(), None)).join().unwrap();
+}
+
+#[test]
+fn test_requests_are_logged_with_their_connection() {
+    let logger = logger();
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+
+    let mut client = Client::connect(addr).unwrap();
+    assert_eq!(client.echo("hello").unwrap(), "hello");
+    assert_eq!(client.add(1, 2).unwrap(), 3);
+    assert!(client.add(i32::MAX, 1).is_err());
+    client.close().unwrap();
+
+    // The connection thread logs its last record once it saw the client leave
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let connection_records = loop {
+        let records = logger.records.lock().unwrap().clone();
+        /* the only client of the test */
+        let connection = records
+            .iter()
+            .find(|record| record.message.contains("New client connected"))
+            .and_then(|record| record.connection.clone());
+        let connection_records: Vec<Captured> = records
+            .into_iter()
+            .filter(|record| connection.is_some() && record.connection == connection)
+            .collect();
+        if connection_records.iter().any(|record| record.message.contains("disconnected")) {
+            break connection_records;
+        }
+        assert!(Instant::now() < deadline, "The server never logged the disconnection");
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    // One record per request, carrying its type, outcome and latency
+    let served: Vec<&Captured> = connection_records
+        .iter()
+        .filter(|record| record.fields.contains_key("message_type"))
+        .collect();
+    let summary: Vec<(&str, &str)> = served
+        .iter()
+        .map(|record| (record.fields["message_type"].as_str(), record.fields["outcome"].as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [("echo_message", "ok"), ("add_request", "ok"), ("add_request", "overflow")]
+    );
+    for record in &served {
+        assert!(record.fields["latency_us"].parse::<u64>().is_ok(), "{:?}", record);
+        assert!(record.fields["request_bytes"].parse::<u64>().unwrap() > 0, "{:?}", record);
+    }
+
+    // The connection is tagged with the address the client connects from
+    let connection = connection_records[0].connection.as_ref().unwrap();
+    assert!(connection.peer.starts_with("127.0.0.1:"), "{:?}", connection);
+    assert_ne!(connection.peer, addr.to_string());
+
+    // Serving clients is quiet at the default level
+    let loud: Vec<&Captured> = connection_records.iter().filter(|record| record.level <= Level::Info).collect();
+    assert!(loud.is_empty(), "Logged at info or above while serving a client: {:?}", loud);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    let records = logger.records.lock().unwrap();
+    assert!(!records.iter().any(|record| record.message.contains("No incoming connections")));
+}
@@ -0,0 +1,115 @@
+use embedded_recruitment_task::{
+    client::ClientError,
+    framing,
+    message::{client_message, ClientMessage, ServerMessage},
+    transport::{MemoryListener, MemoryStream, Transport},
+};
+// use log::error;
+// use log::info;
+use prost::Message;
+use std::io::Write;
+use std::{io, time::Duration};
+
+// Client of a server accepting from an in-memory listener, no port involved
+pub struct Client {
+    listener: MemoryListener,
+    timeout: Duration,
+    stream: Option<MemoryStream>,
+}
+
+impl Client {
+    pub fn new(listener: &MemoryListener, timeout_ms: u64) -> Self {
+        Client {
+            listener: listener.clone(),
+            timeout: Duration::from_millis(timeout_ms),
+            stream: None,
+        }
+    }
+
+    // connect the client to the server
+    pub fn connect(&mut self,_id:i32) -> Result<(), ClientError> {
+        let stream = self.listener.connect();
+        // Waiting for a response is bounded too, `receive` fails with a timeout instead of blocking forever
+        stream.set_timeout(Some(self.timeout));
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    // disconnect the client
+    pub fn disconnect(&mut self,_id:i32) -> Result<(), ClientError> {
+        if let Some(stream) = self.stream.take() {
+            stream.shutdown()?;
+        }
+        Ok(())
+    }
+
+    // generic message to send message to the server
+    pub fn send(&mut self, message: client_message::Message,id:i32) -> Result<(), ClientError> {
+        if let Some(ref mut stream) = self.stream {
+            // Encode the message to a frame (length prefix + ClientMessage)
+            let buffer = framing::encode_frame(&ClientMessage {
+                message: Some(message),
+                ..Default::default()
+            });
+            
+            // Print the size of the buffer
+            println!("client-{}: Buffer size: {} bytes", id, buffer.len());
+            
+            // Send the buffer to the server
+            stream.write_all(&buffer)?;
+            stream.flush()?;
+
+            println!("client-{}:Sent message: {:?}",id, buffer);
+            Ok(())
+        } else {
+            println!("msh 3aaaaaarf<=============");
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "No active connection",
+            )
+            .into())
+        }
+    }
+
+    pub fn receive(&mut self,id:i32) -> Result<ServerMessage, ClientError> {
+        println!("Function 'receive' started.");
+    
+        if let Some(ref mut stream) = self.stream {
+            println!("Stream is active. Attempting to read from the server...");
+    
+            // Read one whole frame from the stream
+            let frame = match framing::read_frame(stream, framing::DEFAULT_MAX_FRAME_SIZE) {
+                Ok(frame) => {
+                    // Successfully read data
+                    println!("client-{}: Read operation completed. Bytes read: {}",id, frame.len());
+                    frame
+                }
+                Err(e) => {
+                    // Keep the cause: a timeout, the server going away, a bad frame...
+                    let e = ClientError::from(e);
+                    println!("client-{}: Read error: {}",id, e);
+                    return Err(e);
+                }
+            };
+    
+            // println!("Received {} bytes from the server.", bytes_read);
+    
+            // Decode the received message
+            println!("Decoding the received message...");
+            let message = ServerMessage::decode(frame.as_slice()).map_err(|e| {
+                println!("Failed to decode ServerMessage: {}", e);
+                ClientError::Decode(e)
+            })?;
+    
+            println!("Message decoded successfully.");
+            Ok(message)
+        } else {
+            println!("Receive function: No active connection.");
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "No active connection",
+            )
+            .into())
+        }
+    }
+}
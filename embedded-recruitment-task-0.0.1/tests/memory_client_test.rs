@@ -0,0 +1,270 @@
+use embedded_recruitment_task::{
+    message::{client_message, server_message, AddRequest, EchoMessage},
+    server::{Server, ServerConfig},
+    transport::MemoryListener,
+};
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+mod memory_client;
+
+/*
+    Each test starts its own server on an in-memory listener, so that the tests can run in
+    parallel without any port. Returns the server, its listener and the thread running it.
+*/
+fn setup_server_thread(id:usize) -> (Arc<Server<MemoryListener>>, MemoryListener, JoinHandle<()>) {
+    println!("setup_server_thread is called from test number {}",id+1);
+    let listener = MemoryListener::new(64 * 1024);
+    let server = Arc::new(Server::with_acceptor(listener.clone(), ServerConfig::default()));
+    let handle = {
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            // Server running on a separate thread
+            server.run(id).unwrap();
+        })
+    };
+    (server, listener, handle)
+}
+
+#[test]
+fn test_client_connection() {
+    // Set up the server in a separate thread
+    let (server, listener, handle) = setup_server_thread(0);
+
+    // Create and connect the client
+    let mut client = memory_client::Client::new(&listener, 1000);
+    assert!(client.connect(1).is_ok(), "Failed to connect to the server");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect(1).is_ok(),
+        "Failed to disconnect from the server"
+    );
+    
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_client_echo_message() {
+    // Set up the server in a separate thread
+    let (server, listener, handle) = setup_server_thread(1);
+
+    // Create and connect the client
+    let mut client = memory_client::Client::new(&listener, 2000);
+    assert!(client.connect(2).is_ok(), "Failed to connect to the server");
+
+    // Prepare the message
+    let echo_message = EchoMessage {
+        content: "Hello, World!".to_string(),
+    };
+    let message = client_message::Message::EchoMessage(echo_message.clone());
+
+    // Lock the mutex before calling send
+    // let lock = CLIENT_MUTEX.lock().unwrap();
+    assert!(client.send(message,2).is_ok(), "Failed to send message");
+
+    let response = client.receive(2);
+    assert!(
+        response.is_ok(),
+        "client-2: Failed to receive response for EchoMessage: {:?}",
+        response.unwrap_err() // Log the error
+    );
+
+    match response.unwrap().message {
+        Some(server_message::Message::EchoMessage(echo)) => {
+            assert_eq!(
+                echo.content, echo_message.content,
+                "Echoed message content does not match"
+            );
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect(2).is_ok(),
+        "Failed to disconnect from the server"
+    );
+    
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_multiple_echo_messages() {
+    // Set up the server in a separate thread
+    let (server, listener, handle) = setup_server_thread(2);
+
+    // Create and connect the client
+    let mut client = memory_client::Client::new(&listener, 1000);
+    assert!(client.connect(3).is_ok(), "Failed to connect to the server");
+
+    // Prepare multiple messages
+    let messages = vec![
+        "Hello, World!".to_string(),
+        "How are you?".to_string(),
+        "Goodbye!".to_string(),
+    ];
+
+    // Send and receive multiple messages
+    for message_content in messages {
+        let echo_message = EchoMessage {
+            content: message_content.clone(),
+        };
+        let message = client_message::Message::EchoMessage(echo_message);
+
+        assert!(client.send(message,3).is_ok(), "Failed to send message");
+
+        let response = client.receive(3);
+        assert!(
+            response.is_ok(),
+            "Failed to receive response for EchoMessage"
+        );
+
+        match response.unwrap().message {
+            Some(server_message::Message::EchoMessage(echo)) => {
+                assert_eq!(
+                    echo.content, message_content,
+                    "Echoed message content does not match"
+                );
+            }
+            _ => panic!("Expected EchoMessage, but received a different message"),
+        }
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect(3).is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_multiple_clients() {
+    // Set up the server in a separate thread
+    let (server, listener, handle) = setup_server_thread(3);
+
+    // Create and connect multiple clients
+    let mut clients = [
+        memory_client::Client::new(&listener, 1000),
+        memory_client::Client::new(&listener, 1000),
+        memory_client::Client::new(&listener, 1000),
+    ];
+
+    for client in clients.iter_mut() {
+        assert!(client.connect(4).is_ok(), "Failed to connect to the server");
+    }
+
+    // Prepare multiple messages
+    let messages = vec![
+        "Hello, World!".to_string(),
+        "How are you?".to_string(),
+        "Goodbye!".to_string(),
+    ];
+
+    // Send and receive multiple messages for each client
+    for message_content in messages {
+        let echo_message = EchoMessage {
+            content: message_content.clone(),
+        };
+        let message = client_message::Message::EchoMessage(echo_message.clone());
+
+        for client in clients.iter_mut() {
+            assert!(
+                client.send(message.clone(),4).is_ok(),
+                "Failed to send message"
+            );
+
+            // Receive the echoed message
+            let response = client.receive(4);
+            assert!(
+                response.is_ok(),
+                "Failed to receive response for EchoMessage"
+            );
+
+            match response.unwrap().message {
+                Some(server_message::Message::EchoMessage(echo)) => {
+                    assert_eq!(
+                        echo.content, message_content,
+                        "Echoed message content does not match"
+                    );
+                }
+                _ => panic!("Expected EchoMessage, but received a different message"),
+            }
+        }
+    }
+
+    // Disconnect the clients
+    for client in clients.iter_mut() {
+        assert!(
+            client.disconnect(4).is_ok(),
+            "Failed to disconnect from the server"
+        );
+    }
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+#[test]
+fn test_client_add_request() {
+    // Set up the server in a separate thread
+    let (server, listener, handle) = setup_server_thread(4);
+
+    // Create and connect the client
+    let mut client = memory_client::Client::new(&listener, 1000);
+    assert!(client.connect(5).is_ok(), "Failed to connect to the server");
+
+    // Prepare the message
+    let add_request = AddRequest { a: 10, b: 20 };
+    let message = client_message::Message::AddRequest(add_request);
+
+    // Send the message to the server
+    assert!(client.send(message, 5).is_ok(), "Failed to send message");
+    
+    // Receive the response
+    let response = {
+        client.receive(5)
+    };
+    assert!(response.is_ok(), "Failed to receive response for AddRequest");
+    
+    match response.unwrap().message {
+        Some(server_message::Message::AddResponse(add_response)) => {
+            assert_eq!(
+                add_response.result,
+                add_request.a + add_request.b,
+                "AddResponse result does not match"
+            );
+        }
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+    // Disconnect the client
+    assert!(client.disconnect(5).is_ok(), "Failed to disconnect from the server");
+    
+    // Stop the server and wait for the thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
@@ -0,0 +1,228 @@
+use embedded_recruitment_task::{
+    client::Client,
+    framing,
+    message::{client_message, ClientMessage, EchoMessage},
+    server::{Server, ServerConfig},
+};
+use prost::Message as _;
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+mod common;
+
+/* Starts a server exposing its metrics, returns it with the TCP and the metrics addresses */
+fn setup_server(id: usize, config: ServerConfig) -> (Arc<Server>, SocketAddr, SocketAddr, JoinHandle<()>) {
+    let (server, addr, handle) = common::start_server(id, config, |server| {
+        server.enable_metrics("127.0.0.1:0").expect("Failed to bind the metrics listener");
+    });
+    let metrics_addr = server.metrics_addr().unwrap();
+    (server, addr, metrics_addr, handle)
+}
+
+/* Sends a bare HTTP/1.1 GET and returns the status code, the head and the body */
+fn get(addr: SocketAddr, path: &str) -> (u16, String, String) {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the metrics endpoint");
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("Failed to read HTTP response");
+    let (head, body) = response.split_once("\r\n\r\n").expect("Malformed HTTP response");
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .expect("Missing HTTP status code");
+    (status, head.to_string(), body.to_string())
+}
+
+/* Scrapes /metrics into `name{labels}` -> value, checking every sample has its # TYPE line */
+fn scrape(addr: SocketAddr) -> BTreeMap<String, f64> {
+    let (status, head, body) = get(addr, "/metrics");
+    assert_eq!(status, 200);
+    assert!(head.to_lowercase().contains("content-type: text/plain; version=0.0.4"), "{}", head);
+
+    let mut types = Vec::new();
+    let mut samples = BTreeMap::new();
+    for line in body.lines() {
+        if let Some(declaration) = line.strip_prefix("# TYPE ") {
+            types.push(declaration.split_whitespace().next().unwrap().to_string());
+            continue;
+        }
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        let (series, value) = line.rsplit_once(' ').expect("Malformed sample");
+        let name = series.split('{').next().unwrap();
+        assert!(
+            types.iter().any(|declared| name.starts_with(declared.as_str())),
+            "{} has no # TYPE line",
+            name
+        );
+        samples.insert(series.to_string(), value.parse().expect("Malformed value"));
+    }
+    samples
+}
+
+/* Scrapes until `series` reaches `value` */
+fn wait_for(addr: SocketAddr, series: &str, value: f64) -> BTreeMap<String, f64> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let samples = scrape(addr);
+        if samples.get(series) == Some(&value) {
+            return samples;
+        }
+        assert!(Instant::now() < deadline, "{} never reached {}: {:?}", series, value, samples.get(series));
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_metrics_count_connections_requests_and_bytes() {
+    let (server, addr, metrics_addr, handle) = setup_server(0, ServerConfig::default());
+
+    let samples = scrape(metrics_addr);
+    assert_eq!(samples["server_connections_accepted_total"], 0.0);
+    assert_eq!(samples["server_connections_active"], 0.0);
+    assert_eq!(samples["server_request_duration_seconds_count"], 0.0);
+
+    let mut client = Client::connect(addr).unwrap();
+    assert_eq!(client.echo("hello").unwrap(), "hello");
+    assert_eq!(client.add(1, 2).unwrap(), 3);
+    assert!(client.add(i32::MAX, 1).is_err());
+
+    let samples = scrape(metrics_addr);
+    assert_eq!(samples["server_connections_accepted_total"], 1.0);
+    assert_eq!(samples["server_connections_active"], 1.0);
+    assert_eq!(samples["server_requests_total{type=\"echo_message\"}"], 1.0);
+    assert_eq!(samples["server_requests_total{type=\"add_request\"}"], 2.0);
+    assert_eq!(samples["server_errors_total{kind=\"overflow\"}"], 1.0);
+    assert!(samples["server_received_bytes_total"] > 0.0);
+    assert!(samples["server_sent_bytes_total"] > 0.0);
+
+    // Three requests in the latency histogram, the buckets are cumulative
+    assert_eq!(samples["server_request_duration_seconds_count"], 3.0);
+    assert_eq!(samples["server_request_duration_seconds_bucket{le=\"+Inf\"}"], 3.0);
+    let buckets: Vec<f64> = samples
+        .iter()
+        .filter(|(series, _)| series.starts_with("server_request_duration_seconds_bucket{le=\"0"))
+        .map(|(_, count)| *count)
+        .collect();
+    assert!(!buckets.is_empty());
+    assert!(buckets.iter().all(|count| *count <= 3.0));
+    assert!(samples["server_request_duration_seconds_sum"] >= 0.0);
+
+    // The library reports the same counters
+    assert_eq!(server.stats().requests["add_request"], 2);
+
+    client.close().unwrap();
+    wait_for(metrics_addr, "server_connections_active", 0.0);
+
+    let (status, _, _) = get(metrics_addr, "/other");
+    assert_eq!(status, 404);
+
+    // Scrapes with a query string are answered the same
+    let (status, _, body) = get(metrics_addr, "/metrics?name[]=server_connections_active");
+    assert_eq!(status, 200);
+    assert!(body.contains("server_connections_active 0"), "{}", body);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_metrics_count_rejections_and_timeouts() {
+    let config = ServerConfig {
+        max_connections: Some(1),
+        read_timeout: Some(Duration::from_millis(200)),
+        ..ServerConfig::default()
+    };
+    let (server, addr, metrics_addr, handle) = setup_server(1, config);
+
+    // The second client goes over max_connections
+    let mut first = Client::connect(addr).unwrap();
+    assert_eq!(first.echo("first").unwrap(), "first");
+    let _second = TcpStream::connect(addr).unwrap();
+    wait_for(metrics_addr, "server_connections_rejected_total", 1.0);
+
+    // The first one then stays silent for longer than the read timeout
+    let samples = wait_for(metrics_addr, "server_errors_total{kind=\"read_timeout\"}", 1.0);
+    assert_eq!(samples["server_connections_accepted_total"], 1.0);
+    wait_for(metrics_addr, "server_connections_active", 0.0);
+    assert!(first.echo("too late").is_err());
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_metrics_count_websocket_sessions() {
+    let config = ServerConfig {
+        max_connections: Some(1),
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(2, config, |server| {
+        server.enable_metrics("127.0.0.1:0").unwrap();
+        server.enable_websocket("127.0.0.1:0").unwrap();
+    });
+    let (metrics_addr, websocket_addr) = (server.metrics_addr().unwrap(), server.websocket_addr().unwrap());
+
+    // A WebSocket session is counted like a TCP connection, bytes included
+    let (mut websocket, _) = tungstenite::connect(format!("ws://{}", websocket_addr)).unwrap();
+    let request = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "counted".to_string(),
+        })),
+        ..Default::default()
+    };
+    websocket.send(tungstenite::Message::binary(request.encode_to_vec())).unwrap();
+    websocket.read().unwrap();
+    let samples = wait_for(metrics_addr, "server_connections_active", 1.0);
+    assert_eq!(samples["server_connections_accepted_total"], 1.0);
+    assert!(samples["server_received_bytes_total"] > 0.0);
+    assert!(samples["server_sent_bytes_total"] > 0.0);
+
+    // It takes the only slot of max_connections, a TCP client is rejected
+    let _rejected = TcpStream::connect(addr).unwrap();
+    wait_for(metrics_addr, "server_connections_rejected_total", 1.0);
+
+    // Once it left, the slot is free again
+    websocket.close(None).unwrap();
+    let _ = websocket.read();
+    wait_for(metrics_addr, "server_connections_active", 0.0);
+    let mut client = Client::connect(addr).unwrap();
+    assert_eq!(client.echo("served").unwrap(), "served");
+    client.close().unwrap();
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_metrics_answer_during_the_drain() {
+    let (server, addr, metrics_addr, handle) = setup_server(3, ServerConfig::default());
+
+    // A request is half sent when the server is stopped
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let request = framing::encode_frame(&ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "draining".to_string(),
+        })),
+        ..Default::default()
+    });
+    stream.write_all(&request[..3]).unwrap();
+    wait_for(metrics_addr, "server_connections_active", 1.0);
+    server.stop();
+
+    // The drain can be watched until the connection is done
+    let samples = scrape(metrics_addr);
+    assert_eq!(samples["server_connections_active"], 1.0);
+    stream.write_all(&request[3..]).unwrap();
+    framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
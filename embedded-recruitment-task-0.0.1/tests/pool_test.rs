@@ -0,0 +1,170 @@
+use embedded_recruitment_task::{
+    client::ClientError,
+    pool::{Pool, PoolConfig, PoolStatus},
+    server::{Server, ServerConfig},
+};
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+mod common;
+
+fn stop_server(server: Arc<Server>, handle: JoinHandle<()>) {
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_checkout_waits_for_a_connection_up_to_the_timeout() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+    let pool = Pool::new(
+        addr,
+        PoolConfig {
+            min_size: 1,
+            max_size: 1,
+            checkout_timeout: Duration::from_millis(200),
+            ..PoolConfig::default()
+        },
+    )
+    .unwrap();
+
+    let mut first = pool.get().unwrap();
+    assert_eq!(first.add(1, 2).unwrap(), 3);
+    assert_eq!(pool.status(), PoolStatus { idle: 0, in_use: 1 });
+
+    // The only connection is checked out, the next checkout gives up after the timeout
+    let start = Instant::now();
+    match pool.get() {
+        Err(ClientError::CheckoutTimeout(timeout)) => assert_eq!(timeout, Duration::from_millis(200)),
+        Err(e) => panic!("Expected a checkout timeout, got {}", e),
+        Ok(_) => panic!("Expected a checkout timeout, got a connection"),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // A waiting checkout gets the connection as soon as it is given back
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.get().map(|mut client| client.echo("reused").unwrap()))
+    };
+    thread::sleep(Duration::from_millis(50));
+    drop(first);
+    assert_eq!(waiter.join().unwrap().unwrap(), "reused");
+    assert_eq!(pool.status(), PoolStatus { idle: 1, in_use: 0 });
+
+    stop_server(server, handle);
+}
+
+#[test]
+fn test_broken_connections_are_replaced() {
+    // The server closes connections idle for more than 100ms
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(100)),
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(1, config, |_| {});
+    let pool = Pool::new(
+        addr,
+        PoolConfig {
+            min_size: 2,
+            max_size: 2,
+            health_check_interval: Some(Duration::ZERO),
+            ..PoolConfig::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(pool.status(), PoolStatus { idle: 2, in_use: 0 });
+
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(server.stats().read_timeouts, 2);
+
+    // Both idle connections fail their ping, the checkout opens a new one instead
+    let mut client = pool.get().unwrap();
+    assert_eq!(client.echo("fresh").unwrap(), "fresh");
+    drop(client);
+
+    // The other dead connection was replaced in the background to keep min_size open
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while pool.status() != (PoolStatus { idle: 2, in_use: 0 }) {
+        assert!(Instant::now() < deadline, "The pool stayed at {:?}", pool.status());
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // A connection discarded explicitly is replaced too
+    pool.get().unwrap().discard();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while pool.status() != (PoolStatus { idle: 2, in_use: 0 }) {
+        assert!(Instant::now() < deadline, "The pool stayed at {:?}", pool.status());
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    stop_server(server, handle);
+}
+
+#[test]
+fn test_shared_between_threads() {
+    let (server, addr, handle) = common::start_server(2, ServerConfig::default(), |_| {});
+    let pool = Pool::new(
+        addr,
+        PoolConfig {
+            min_size: 0,
+            max_size: 3,
+            ..PoolConfig::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(pool.status(), PoolStatus { idle: 0, in_use: 0 });
+
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for i in 0..20 {
+                    let mut client = pool.get().unwrap();
+                    assert_eq!(client.add(thread, i).unwrap(), thread + i);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        assert!(thread.join().is_ok(), "A client thread panicked");
+    }
+
+    let status = pool.status();
+    assert_eq!(status.in_use, 0);
+    assert!(status.idle >= 1 && status.idle <= 3, "{:?}", status);
+
+    stop_server(server, handle);
+}
+
+#[test]
+fn test_pings_are_health_checks() {
+    let (server, addr, handle) = common::start_server(3, ServerConfig::default(), |_| {});
+    let pool = Pool::new(
+        addr,
+        PoolConfig {
+            min_size: 1,
+            max_size: 1,
+            health_check_interval: Some(Duration::ZERO),
+            ..PoolConfig::default()
+        },
+    )
+    .unwrap();
+
+    // Every checkout pings the idle connection first
+    for _ in 0..3 {
+        let mut client = pool.get().unwrap();
+        assert_eq!(client.add(1, 1).unwrap(), 2);
+    }
+    pool.get().unwrap().ping().unwrap();
+
+    // The pings are counted as health checks, not as requests of the echo service
+    let requests = server.stats().requests;
+    assert_eq!(requests.get("add_request"), Some(&3), "{:?}", requests);
+    assert_eq!(requests.get("echo_message"), None, "{:?}", requests);
+    assert!(requests.get("health_check").is_some_and(|&count| count >= 4), "{:?}", requests);
+
+    drop(pool);
+    stop_server(server, handle);
+}
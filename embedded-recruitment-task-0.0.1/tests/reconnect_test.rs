@@ -0,0 +1,68 @@
+use embedded_recruitment_task::{
+    client::{Backoff, ClientConfig, ClientError, ReconnectingClient},
+    server::{Server, ServerConfig},
+};
+use std::{
+    net::TcpListener,
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+fn fast_backoff(max_attempts: u32) -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(20),
+        max: Duration::from_millis(200),
+        multiplier: 2.0,
+        max_attempts,
+    }
+}
+
+#[test]
+fn test_requests_survive_a_server_restart() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+    let mut client = ReconnectingClient::new(addr, ClientConfig::default(), fast_backoff(20)).unwrap();
+    assert_eq!(client.echo("before").unwrap(), "before");
+
+    // Stop the server and release its port, the client's connection is now dead
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    drop(server);
+
+    // Bring a new server up on the same port while the client is already retrying
+    let restart = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        common::run_server(Server::new(&addr.to_string()).expect("Failed to start server"), 1)
+    });
+    assert_eq!(client.add(40, 2).unwrap(), 42, "The request was not retried on the new server");
+    assert_eq!(client.reconnects(), 1);
+
+    let (server, _, handle) = restart.join().unwrap();
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_attempts_budget() {
+    // Nobody listens on a port that was just released
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut client = ReconnectingClient::new(addr, ClientConfig::default(), fast_backoff(4)).unwrap();
+
+    let started = Instant::now();
+    let error = client.echo("anyone?").unwrap_err();
+    assert!(matches!(error, ClientError::Io(_)), "{:?}", error);
+    assert!(!client.is_connected());
+
+    // 3 waits of at least half of 20, 40 and 80 ms, at most their full length
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(70), "Gave up too early: {:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "Waited too long: {:?}", elapsed);
+}
+
+#[test]
+fn test_backoff_delays() {
+    let backoff = fast_backoff(10);
+    let delays: Vec<u64> = (1..=6).map(|failures| backoff.delay(failures).as_millis() as u64).collect();
+    assert_eq!(delays, [20, 40, 80, 160, 200, 200]);
+}
@@ -0,0 +1,152 @@
+use embedded_recruitment_task::{
+    framing,
+    message::{client_message, AddRequest, ClientMessage, EchoMessage, StatsRequest},
+    recording::{self, Direction},
+    server::{ServerConfig, Services},
+};
+use std::{
+    io::Write,
+    net::TcpStream,
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+mod common;
+
+fn replay(recording: &Path, addr: impl ToString) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_replay"))
+        .arg(recording)
+        .args(["--addr", &addr.to_string()])
+        .output()
+        .expect("Failed to start the replay binary");
+    (output.status.code(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn test_record_and_replay() {
+    let path = std::env::temp_dir().join(format!("recording_test_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Record one session with an Echo and an Add
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |server| {
+        server.enable_recording(&path).expect("Failed to open the recording");
+    });
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    for message in [
+        client_message::Message::EchoMessage(EchoMessage {
+            content: "recorded".to_string(),
+        }),
+        client_message::Message::AddRequest(AddRequest { a: 20, b: 22 }),
+    ] {
+        let request = framing::encode_frame(&ClientMessage { message: Some(message), ..Default::default() });
+        stream.write_all(&request).unwrap();
+        framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    }
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    let records = recording::read_recording(&path).expect("Failed to read the recording");
+    let directions: Vec<Direction> = records.iter().map(|record| record.direction).collect();
+    assert_eq!(directions, [Direction::Request, Direction::Response, Direction::Request, Direction::Response]);
+    assert!(records.iter().all(|record| record.session == 1));
+    assert_eq!(records[3].message, serde_json::json!({"add_response": {"result": 42}}));
+    assert!(records.windows(2).all(|pair| pair[0].timestamp_us <= pair[1].timestamp_us));
+
+    // The same server version answers the same way
+    let (server, addr, handle) = common::start_server(1, ServerConfig::default(), |_| {});
+    let (code, stdout) = replay(&path, addr);
+    assert_eq!(code, Some(0), "{}", stdout);
+    assert!(stdout.contains("replayed 2 requests of 1 sessions, 0 differences"), "{}", stdout);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // A server without the add service differs on the second request
+    let config = ServerConfig {
+        services: Services { echo: true, add: false },
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(2, config, |_| {});
+    let (code, stdout) = replay(&path, addr);
+    assert_eq!(code, Some(2), "{}", stdout);
+    assert!(stdout.contains("request 2"), "{}", stdout);
+    assert!(stdout.contains(r#"  recorded: {"add_response":{"result":42}}"#), "{}", stdout);
+    assert!(stdout.contains(r#""code":"service_disabled""#), "{}", stdout);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_replay_after_the_deadline() {
+    let path = std::env::temp_dir().join(format!("recording_deadline_test_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Record a request sent with a deadline, the way the client does with a request_timeout
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |server| {
+        server.enable_recording(&path).expect("Failed to open the recording");
+    });
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let deadline = SystemTime::now() + Duration::from_millis(200);
+    let request = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "in time".to_string(),
+        })),
+        deadline_unix_ms: deadline.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+    };
+    stream.write_all(&framing::encode_frame(&request)).unwrap();
+    framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // Replayed once the recorded deadline passed, the request still gets its 200ms
+    thread::sleep(Duration::from_millis(400));
+    let (server, addr, handle) = common::start_server(1, ServerConfig::default(), |_| {});
+    let (code, stdout) = replay(&path, addr);
+    assert_eq!(code, Some(0), "{}", stdout);
+    assert!(stdout.contains("replayed 1 requests of 1 sessions, 0 differences"), "{}", stdout);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_replay_ignores_the_uptime() {
+    let path = std::env::temp_dir().join(format!("recording_stats_test_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Record a StatsRequest once the server ran for a while
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |server| {
+        server.enable_recording(&path).expect("Failed to open the recording");
+    });
+    thread::sleep(Duration::from_millis(300));
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let request = ClientMessage {
+        message: Some(client_message::Message::StatsRequest(StatsRequest {})),
+        ..Default::default()
+    };
+    stream.write_all(&framing::encode_frame(&request)).unwrap();
+    framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // A new server has another uptime, that is no difference. localhost may resolve to ::1 first,
+    // the replay falls back to 127.0.0.1
+    let (server, addr, handle) = common::start_server(1, ServerConfig::default(), |_| {});
+    let (code, stdout) = replay(&path, format!("localhost:{}", addr.port()));
+    assert_eq!(code, Some(0), "{}", stdout);
+    assert!(stdout.contains("replayed 1 requests of 1 sessions, 0 differences"), "{}", stdout);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    std::fs::remove_file(&path).unwrap();
+}
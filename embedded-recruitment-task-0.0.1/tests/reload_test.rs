@@ -0,0 +1,107 @@
+use embedded_recruitment_task::{
+    config::Config,
+    framing,
+    message::{client_message, server_message, AddRequest, ClientMessage, EchoMessage, ErrorCode, ServerMessage},
+    server::{Server, ServerConfig, Services},
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+mod common;
+
+fn send(stream: &mut TcpStream, message: client_message::Message) -> ServerMessage {
+    let request = ClientMessage { message: Some(message), ..Default::default() };
+    stream.write_all(&framing::encode_frame(&request)).expect("Failed to send message");
+    let frame = framing::read_frame(stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    ServerMessage::decode(frame.as_slice()).expect("Failed to decode ServerMessage")
+}
+
+#[test]
+fn test_reload_applies_to_connected_clients() {
+    let config = ServerConfig {
+        services: Services { echo: true, add: false },
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(0, config.clone(), |_| {});
+
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let add = || client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+    match send(&mut stream, add()).message {
+        Some(server_message::Message::ErrorResponse(error)) => assert_eq!(error.code, ErrorCode::ServiceDisabled as i32),
+        _ => panic!("Expected ErrorResponse, but received a different message"),
+    }
+
+    // Enable the add service and change the number of workers
+    let report = server.reload(ServerConfig {
+        services: Services::default(),
+        workers: 4,
+        ..config
+    });
+    assert_eq!(report.applied, ["services"]);
+    assert_eq!(report.restart_required, ["workers"]);
+    assert_eq!(server.config().workers, 0, "The number of workers can't change while running");
+
+    // The same connection is served with the new settings
+    thread::sleep(Duration::from_millis(300));
+    match send(&mut stream, add()).message {
+        Some(server_message::Message::AddResponse(add)) => assert_eq!(add.result, 3),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+    assert!(server.reload(server.config()).is_empty(), "Nothing changed the second time");
+
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_reloaded_timeout_closes_idle_client() {
+    let (server, addr, handle) = common::start_server(1, ServerConfig::default(), |_| {});
+
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let echo = client_message::Message::EchoMessage(EchoMessage {
+        content: "before".to_string(),
+    });
+    assert!(matches!(send(&mut stream, echo).message, Some(server_message::Message::EchoMessage(_))));
+
+    // The connection opened without a read timeout gets the reloaded one
+    let report = server.reload(ServerConfig {
+        read_timeout: Some(Duration::from_millis(300)),
+        ..server.config()
+    });
+    assert_eq!(report.applied, ["read_timeout"]);
+
+    let mut buffer = [0u8; 16];
+    assert_eq!(stream.read(&mut buffer).expect("Expected a clean close"), 0);
+    assert_eq!(server.stats().read_timeouts, 1, "The read timeout was not counted");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_config_reload_reports_log_level_and_listeners() {
+    let mut running = Config::parse("[listeners]\ntcp = \"127.0.0.1:0\"\n", "running").unwrap();
+    let server = Server::with_config(&running.listeners.tcp, running.server_config()).expect("Failed to start server");
+
+    // The log level is applied right away, another listener address only with a restart
+    let reloaded = Config::parse(
+        "[listeners]\ntcp = \"127.0.0.1:1\"\n[limits]\nmax_connections = 5\n[logging]\nlevel = \"warn\"\n",
+        "reloaded",
+    )
+    .unwrap();
+    let report = reloaded.reload(&server, &mut running);
+    assert_eq!(report.applied, ["max_connections", "logging.level"]);
+    assert_eq!(report.restart_required, ["listeners.tcp"]);
+    assert_eq!(log::max_level(), log::LevelFilter::Warn);
+    assert_eq!(running.logging.level, "warn");
+    assert_eq!(running.listeners.tcp, "127.0.0.1:0", "The listener keeps its address until a restart");
+    assert_eq!(server.config().max_connections, Some(5));
+}
@@ -0,0 +1,239 @@
+#![cfg(unix)]
+
+use embedded_recruitment_task::{
+    framing,
+    message::{client_message, server_message, ClientMessage, EchoMessage, ErrorCode, ServerMessage},
+};
+use prost::Message;
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/* Starts the server binary on a free port with `args` and returns it with the address it printed */
+fn start_server_with(args: &[&str]) -> (Child, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_server"))
+        .args(["--bind", "127.0.0.1:0"])
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start the server binary");
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let addr = lines
+        .by_ref()
+        .map_while(Result::ok)
+        .find_map(|line| line.strip_prefix("listening on ").map(str::to_string))
+        .expect("The server did not print its address");
+
+    // Keep reading the output so the server never blocks on a full pipe
+    thread::spawn(move || lines.for_each(drop));
+    (child, addr)
+}
+
+fn start_server() -> (Child, String) {
+    start_server_with(&[])
+}
+
+fn send_signal(child: &Child, signal: &str) {
+    let status = Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(child.id().to_string())
+        .status()
+        .expect("Failed to run kill");
+    assert!(status.success(), "kill -{} failed", signal);
+}
+
+/* Waits for the process to exit, None if it is still running after `timeout` */
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait().unwrap() {
+            return Some(status);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    None
+}
+
+fn echo_frame(content: &str) -> Vec<u8> {
+    framing::encode_frame(&ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_sigterm_stops_idle_server() {
+    let (mut child, _) = start_server();
+
+    send_signal(&child, "TERM");
+    let status = wait_for_exit(&mut child, Duration::from_secs(5)).expect("The server did not stop");
+    assert_eq!(status.code(), Some(0), "A drained server exits with 0");
+}
+
+#[test]
+fn test_sigint_drains_request_in_flight() {
+    let (mut child, addr) = start_server();
+
+    // Start a request without finishing it
+    let mut stream = TcpStream::connect(&addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let frame = echo_frame("still in flight");
+    let (head, tail) = frame.split_at(4);
+    stream.write_all(head).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    // The server waits for that request before exiting
+    send_signal(&child, "INT");
+    assert!(
+        wait_for_exit(&mut child, Duration::from_millis(500)).is_none(),
+        "The server exited with a request in flight"
+    );
+
+    // Finishing the request gets it answered, then the server is done
+    stream.write_all(tail).unwrap();
+    let response = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    match ServerMessage::decode(response.as_slice()).unwrap().message {
+        Some(server_message::Message::EchoMessage(echo)) => assert_eq!(echo.content, "still in flight"),
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(5)).expect("The server did not stop");
+    assert_eq!(status.code(), Some(0), "A drained server exits with 0");
+}
+
+#[test]
+fn test_second_signal_forces_exit() {
+    let (mut child, addr) = start_server();
+
+    // A request that never completes keeps the drain going
+    let mut stream = TcpStream::connect(&addr).expect("Failed to connect to the server");
+    stream.write_all(&echo_frame("never finished")[..4]).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    send_signal(&child, "TERM");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(5)).expect("The server did not exit");
+    assert_eq!(status.code(), Some(130), "A forced exit uses the exit code 130");
+}
+
+#[test]
+fn test_command_line_options_are_applied() {
+    let (mut child, addr) = start_server_with(&["--workers", "2", "--max-frame-size", "16", "--log-level", "warn"]);
+
+    let mut stream = TcpStream::connect(&addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    // Small enough for --max-frame-size
+    stream.write_all(&echo_frame("short")).unwrap();
+    let response = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    match ServerMessage::decode(response.as_slice()).unwrap().message {
+        Some(server_message::Message::EchoMessage(echo)) => assert_eq!(echo.content, "short"),
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    // Too large for --max-frame-size
+    stream.write_all(&echo_frame("definitely longer than sixteen bytes")).unwrap();
+    let response = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    match ServerMessage::decode(response.as_slice()).unwrap().message {
+        Some(server_message::Message::ErrorResponse(error)) => assert_eq!(error.code, ErrorCode::FrameTooLarge as i32),
+        _ => panic!("Expected ErrorResponse, but received a different message"),
+    }
+
+    drop(stream);
+    send_signal(&child, "TERM");
+    let status = wait_for_exit(&mut child, Duration::from_secs(5)).expect("The server did not stop");
+    assert_eq!(status.code(), Some(0), "A drained server exits with 0");
+}
+
+#[test]
+fn test_invalid_option_is_rejected() {
+    let status = Command::new(env!("CARGO_BIN_EXE_server"))
+        .args(["--log-level", "loud"])
+        .stderr(Stdio::null())
+        .status()
+        .expect("Failed to start the server binary");
+    assert!(!status.success(), "An invalid option must make the server exit with an error");
+}
+
+#[test]
+fn test_config_file_with_command_line_override() {
+    let path = std::env::temp_dir().join(format!("server_binary_test_{}.toml", std::process::id()));
+    fs::write(&path, "[listeners]\ntcp = \"127.0.0.1:1\"\n\n[limits]\nworkers = 3\n\n[services]\nadd = false\n").unwrap();
+
+    // --check-config prints the merged configuration, the CLI --bind wins over the file
+    let output = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg("--config")
+        .arg(&path)
+        .args(["--bind", "127.0.0.1:0", "--check-config"])
+        .output()
+        .expect("Failed to start the server binary");
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success(), "A valid configuration must be accepted");
+    let printed = String::from_utf8(output.stdout).unwrap();
+    assert!(printed.contains("tcp = \"127.0.0.1:0\""), "The CLI option did not override the file:\n{}", printed);
+    assert!(printed.contains("workers = 3"), "The file value was lost:\n{}", printed);
+    assert!(printed.contains("add = false"), "The file value was lost:\n{}", printed);
+}
+
+#[test]
+fn test_check_config_reports_the_offending_key() {
+    let path = std::env::temp_dir().join(format!("server_binary_invalid_{}.toml", std::process::id()));
+    fs::write(&path, "[timeouts]\nwrite_ms = 0\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg("--config")
+        .arg(&path)
+        .arg("--check-config")
+        .output()
+        .expect("Failed to start the server binary");
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "An invalid configuration exits with 1");
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert!(error.contains("timeouts.write_ms"), "The error does not name the key: {}", error);
+}
+
+#[test]
+fn test_sighup_reloads_the_config_file() {
+    let path = std::env::temp_dir().join(format!("server_binary_reload_{}.toml", std::process::id()));
+    fs::write(&path, "[services]\necho = false\n").unwrap();
+    let (mut child, addr) = start_server_with(&["--config", path.to_str().unwrap()]);
+
+    let mut stream = TcpStream::connect(&addr).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&echo_frame("disabled")).unwrap();
+    let response = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    match ServerMessage::decode(response.as_slice()).unwrap().message {
+        Some(server_message::Message::ErrorResponse(error)) => assert_eq!(error.code, ErrorCode::ServiceDisabled as i32),
+        _ => panic!("Expected ErrorResponse, but received a different message"),
+    }
+
+    // Enable echo in the file and reload, the connection stays open
+    fs::write(&path, "[services]\necho = true\n").unwrap();
+    send_signal(&child, "HUP");
+    thread::sleep(Duration::from_millis(500));
+
+    stream.write_all(&echo_frame("enabled")).unwrap();
+    let response = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+    match ServerMessage::decode(response.as_slice()).unwrap().message {
+        Some(server_message::Message::EchoMessage(echo)) => assert_eq!(echo.content, "enabled"),
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+    fs::remove_file(&path).unwrap();
+
+    drop(stream);
+    send_signal(&child, "TERM");
+    let status = wait_for_exit(&mut child, Duration::from_secs(5)).expect("The server did not stop");
+    assert_eq!(status.code(), Some(0), "A drained server exits with 0");
+}
@@ -0,0 +1,87 @@
+use embedded_recruitment_task::{
+    client::Client,
+    framing,
+    message::{client_message, ClientMessage, EchoMessage},
+    server::{Server, ServerConfig},
+};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+fn start(server: &Arc<Server>, id: usize) -> JoinHandle<()> {
+    let server = Arc::clone(server);
+    thread::spawn(move || server.run(id).unwrap())
+}
+
+/* Joins the thread running the server, failing the test if `run` does not return in time */
+fn join_within(handle: JoinHandle<()>, limit: Duration) {
+    let deadline = Instant::now() + limit;
+    while !handle.is_finished() {
+        assert!(Instant::now() < deadline, "The server did not stop within {:?}", limit);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_server_runs_again_after_stop() {
+    let server = Arc::new(Server::with_config("127.0.0.1:0", ServerConfig::default()).expect("Failed to start server"));
+    let addr = server.local_addr().unwrap();
+
+    for round in 0..3 {
+        let handle = start(&server, 0);
+        let mut client = Client::connect(addr).unwrap();
+        assert_eq!(client.echo(&format!("round {}", round)).unwrap(), format!("round {}", round));
+        client.close().unwrap();
+        server.stop();
+        join_within(handle, Duration::from_secs(5));
+    }
+}
+
+#[test]
+fn test_stop_before_run_is_not_lost() {
+    let server = Arc::new(Server::new("127.0.0.1:0").expect("Failed to start server"));
+    server.stop();
+    // `run` returns right away, then the server can be run normally
+    join_within(start(&server, 1), Duration::from_secs(5));
+
+    let handle = start(&server, 1);
+    let mut client = Client::connect(server.local_addr().unwrap()).unwrap();
+    assert_eq!(client.add(2, 3).unwrap(), 5);
+    client.close().unwrap();
+    server.stop();
+    join_within(handle, Duration::from_secs(5));
+}
+
+#[test]
+fn test_stop_with_peer_stalled_in_a_frame() {
+    // No read or drain timeout: only the shutdown itself may give up on the peer
+    let server = Arc::new(Server::with_config("127.0.0.1:0", ServerConfig::default()).expect("Failed to start server"));
+    let handle = start(&server, 2);
+
+    let request = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "never finished".to_string(),
+        })),
+        ..Default::default()
+    };
+    let frame = framing::encode_frame(&request);
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    stream.write_all(&frame[..frame.len() / 2]).expect("Failed to send half a frame");
+    thread::sleep(Duration::from_millis(200));
+
+    server.stop();
+    join_within(handle, Duration::from_secs(5));
+
+    // The connection was closed without an answer
+    stream.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    let mut buffer = [0u8; 16];
+    assert!(
+        matches!(stream.read(&mut buffer), Ok(0) | Err(_)),
+        "Expected the stalled connection to be closed"
+    );
+}
@@ -0,0 +1,97 @@
+use embedded_recruitment_task::{
+    client::Client,
+    server::ServerConfig,
+};
+use serde_json::Value;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+#[test]
+fn test_stats_request() {
+    let (server, addr, handle) = common::start_server(0, ServerConfig::default(), |_| {});
+    thread::sleep(Duration::from_millis(50));
+
+    let mut client = Client::connect(addr).unwrap();
+    assert_eq!(client.echo("hello").unwrap(), "hello");
+    assert_eq!(client.add(1, 2).unwrap(), 3);
+    assert!(client.add(i32::MAX, 1).is_err());
+
+    let stats = client.stats().unwrap();
+    assert_eq!(stats.version, env!("CARGO_PKG_VERSION"));
+    assert!(stats.uptime_ms >= 50, "uptime {}ms", stats.uptime_ms);
+    assert_eq!(stats.active_connections, 1);
+    assert_eq!(stats.requests.get("echo_message"), Some(&1));
+    assert_eq!(stats.requests.get("add_request"), Some(&2));
+    assert_eq!(stats.errors.get("overflow"), Some(&1));
+    // The StatsRequest being answered is not counted yet
+    assert_eq!(stats.requests.get("stats_request"), None);
+
+    // The same counters as the ones of the library (and of the metrics)
+    let snapshot = server.stats();
+    assert_eq!(snapshot.requests["stats_request"], 1);
+    assert_eq!(snapshot.requests["add_request"], stats.requests["add_request"]);
+    assert_eq!(snapshot.errors, stats.errors.clone().into_iter().collect());
+
+    let stats = client.stats().unwrap();
+    assert_eq!(stats.requests.get("stats_request"), Some(&1));
+    client.close().unwrap();
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_stats_count_timeouts() {
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(150)),
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(1, config, |_| {});
+
+    // A client that stays silent is disconnected, then another one asks for the counts
+    let idle = TcpStream::connect(addr).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.stats().read_timeouts == 0 {
+        assert!(Instant::now() < deadline, "The read timeout never fired");
+        thread::sleep(Duration::from_millis(20));
+    }
+    drop(idle);
+
+    let mut client = Client::connect(addr).unwrap();
+    let stats = client.stats().unwrap();
+    assert_eq!(stats.errors.get("read_timeout"), Some(&1));
+    assert_eq!(stats.active_connections, 1);
+    client.close().unwrap();
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_stats_over_http() {
+    let (server, _, handle) = common::start_server(2, ServerConfig::default(), |server| {
+        server.enable_http("127.0.0.1:0").expect("Failed to bind HTTP listener");
+    });
+    let http_addr = server.http_addr().unwrap();
+
+    let mut stream = TcpStream::connect(http_addr).unwrap();
+    write!(stream, "GET /stats HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", http_addr).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").expect("Malformed HTTP response");
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let body: Value = serde_json::from_str(body).expect("Response body is not JSON");
+    let stats = &body["stats_response"];
+    assert_eq!(stats["version"], env!("CARGO_PKG_VERSION"));
+    assert!(stats["uptime_ms"].is_u64(), "{}", body);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,179 @@
+use embedded_recruitment_task::{
+    framing,
+    message::{client_message, server_message, ClientMessage, EchoMessage, ServerMessage},
+    server::{Server, ServerConfig},
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+#[test]
+fn test_stalled_client_is_disconnected() {
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(300)),
+        ..ServerConfig::default()
+    };
+    let (server, _, handle) = common::start_server(0, config, |_| {});
+
+    // Connect and then never send anything
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let started = Instant::now();
+
+    // The server closes the connection once the read timeout expired
+    let mut buffer = [0u8; 16];
+    let bytes_read = stream.read(&mut buffer).expect("Expected a clean close, not an error");
+    assert_eq!(bytes_read, 0, "Expected the server to close the connection");
+    assert!(
+        started.elapsed() >= Duration::from_millis(300),
+        "The connection was closed before the read timeout expired"
+    );
+    assert_eq!(server.stats().read_timeouts, 1, "The read timeout was not counted");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_active_client_is_kept() {
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(500)),
+        write_timeout: Some(Duration::from_millis(500)),
+        request_timeout: Some(Duration::from_millis(500)),
+        ..ServerConfig::default()
+    };
+    let (server, _, handle) = common::start_server(1, config, |_| {});
+
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    // Requests sent more often than the read timeout keep the connection open
+    for content in ["first", "second", "third"] {
+        thread::sleep(Duration::from_millis(200));
+        let request = ClientMessage {
+            message: Some(client_message::Message::EchoMessage(EchoMessage {
+                content: content.to_string(),
+            })),
+            ..Default::default()
+        };
+        stream.write_all(&framing::encode_frame(&request)).expect("Failed to send message");
+
+        let frame = framing::read_frame(&mut stream, framing::DEFAULT_MAX_FRAME_SIZE).expect("Failed to receive response");
+        let response = ServerMessage::decode(frame.as_slice()).expect("Failed to decode ServerMessage");
+        match response.message {
+            Some(server_message::Message::EchoMessage(echo)) => {
+                assert_eq!(echo.content, content, "Echoed message content does not match");
+            }
+            _ => panic!("Expected EchoMessage, but received a different message"),
+        }
+    }
+    assert_eq!(server.stats().read_timeouts, 0, "No timeout was expected");
+
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+/* An EchoMessage request of `size` bytes of content, framed */
+fn echo_frame(size: usize) -> Vec<u8> {
+    let request = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "x".repeat(size),
+        })),
+        ..Default::default()
+    };
+    framing::encode_frame(&request)
+}
+
+/* Polls the server counters until `done` holds, for at most `limit` */
+fn wait_for(server: &Server, limit: Duration, done: impl Fn(&Server) -> bool) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < limit {
+        if done(server) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    done(server)
+}
+
+#[test]
+fn test_client_not_reading_hits_write_timeout() {
+    let config = ServerConfig {
+        write_timeout: Some(Duration::from_millis(300)),
+        ..ServerConfig::default()
+    };
+    let (server, _, handle) = common::start_server(2, config, |_| {});
+
+    // Send requests and never read the answers until the socket buffers are full both ways
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    stream.set_write_timeout(Some(Duration::from_millis(200))).unwrap();
+    let frame = echo_frame(16 * 1024);
+    let writer = thread::spawn(move || {
+        while stream.write_all(&frame).is_ok() {}
+        // keep the connection open, closing it would end it before the server times out
+        stream
+    });
+
+    assert!(
+        wait_for(&server, Duration::from_secs(10), |server| server.stats().write_timeouts == 1),
+        "The write timeout was not hit"
+    );
+    assert_eq!(server.stats().read_timeouts, 0, "Only a write timeout was expected");
+    assert_eq!(server.stats().request_timeouts, 0, "Only a write timeout was expected");
+
+    assert!(writer.join().is_ok(), "Writer thread panicked");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_slow_request_hits_request_timeout() {
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_secs(1)),
+        request_timeout: Some(Duration::from_millis(400)),
+        ..ServerConfig::default()
+    };
+    let (server, _, handle) = common::start_server(3, config, |_| {});
+
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    let started = Instant::now();
+
+    // Trickle a request byte by byte: the read timeout never expires but the request takes too long
+    let mut closed = false;
+    for byte in echo_frame(1024) {
+        if stream.write_all(&[byte]).is_err() {
+            closed = true;
+            break;
+        }
+        let mut buffer = [0u8; 16];
+        match stream.read(&mut buffer) {
+            Ok(0) => {
+                closed = true;
+                break;
+            }
+            Ok(_) => panic!("The request was answered before it was complete"),
+            Err(_) => {} // nothing to read yet, keep sending
+        }
+        if started.elapsed() > Duration::from_secs(5) {
+            break;
+        }
+    }
+    assert!(closed, "The server did not close the slow request");
+    assert!(
+        started.elapsed() >= Duration::from_millis(400),
+        "The connection was closed before the request timeout expired"
+    );
+    assert_eq!(server.stats().request_timeouts, 1, "The request timeout was not counted");
+    assert_eq!(server.stats().read_timeouts, 0, "Only a request timeout was expected");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,192 @@
+use embedded_recruitment_task::{
+    framing::{self, FrameDecoder},
+    message::{client_message, server_message, AddRequest, ClientMessage, EchoMessage, ServerMessage},
+    server::{self, Server, ServerConfig, ServerError},
+    stats::{ServerStats, TimeoutKind},
+    transport::{self, MemoryListener, MemoryStream, Transport},
+};
+use prost::Message;
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+fn echo(content: &str) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+fn add(a: i32, b: i32) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a, b })),
+        ..Default::default()
+    }
+}
+
+/* A handler serving the server end of an in-memory pipe, the other end is returned */
+fn memory_handler(config: ServerConfig, capacity: usize) -> (MemoryStream, server::Client<MemoryStream>) {
+    let (peer, stream) = transport::duplex(capacity);
+    peer.set_timeout(Some(Duration::from_secs(5)));
+    (peer, server::Client::with_config(stream, config, Arc::new(ServerStats::default())))
+}
+
+/* Lets the handler work until `count` responses can be read from `peer` */
+fn responses(handler: &mut server::Client<MemoryStream>, peer: &mut MemoryStream, count: usize) -> Vec<ServerMessage> {
+    let mut decoder = FrameDecoder::new(framing::DEFAULT_MAX_FRAME_SIZE);
+    let mut responses = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while responses.len() < count {
+        assert!(Instant::now() < deadline, "Got {} responses out of {}", responses.len(), count);
+        handler.handle(0).unwrap();
+        let mut buffer = vec![0u8; peer.available()];
+        peer.read_exact(&mut buffer).unwrap();
+        decoder.extend(&buffer);
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            responses.push(ServerMessage::decode(frame.as_slice()).unwrap());
+        }
+    }
+    responses
+}
+
+fn echoed(response: &ServerMessage) -> &str {
+    match &response.message {
+        Some(server_message::Message::EchoMessage(echo)) => &echo.content,
+        other => panic!("Expected an EchoMessage, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_requests_over_memory_pipe() {
+    let (mut peer, mut handler) = memory_handler(ServerConfig::default(), 64 * 1024);
+    assert_eq!(Transport::peer(&peer), "memory-a");
+
+    peer.write_all(&framing::encode_frame(&echo("hello"))).unwrap();
+    let response = responses(&mut handler, &mut peer, 1);
+    assert_eq!(echoed(&response[0]), "hello");
+
+    peer.write_all(&framing::encode_frame(&add(20, 22))).unwrap();
+    match &responses(&mut handler, &mut peer, 1)[0].message {
+        Some(server_message::Message::AddResponse(add)) => assert_eq!(add.result, 42),
+        other => panic!("Expected an AddResponse, got {:?}", other),
+    }
+
+    // Pipelined requests written in one go, the last one split in two writes
+    let mut bytes = framing::encode_frame(&echo("first"));
+    bytes.extend(framing::encode_frame(&echo("second")));
+    let last = framing::encode_frame(&echo("third"));
+    bytes.extend(&last[..3]);
+    peer.write_all(&bytes).unwrap();
+    let answered = responses(&mut handler, &mut peer, 2);
+    assert_eq!(answered.iter().map(echoed).collect::<Vec<_>>(), ["first", "second"]);
+    peer.write_all(&last[3..]).unwrap();
+    assert_eq!(echoed(&responses(&mut handler, &mut peer, 1)[0]), "third");
+
+    // The peer leaving ends the session without an error
+    drop(peer);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !handler.is_closed() {
+        assert!(Instant::now() < deadline, "The handler did not see the peer leave");
+        handler.handle(0).unwrap();
+    }
+}
+
+#[test]
+fn test_timeouts_over_memory_pipe() {
+    // A peer that sends nothing runs into the read timeout
+    let config = ServerConfig {
+        read_timeout: Some(Duration::from_millis(100)),
+        ..ServerConfig::default()
+    };
+    let (peer, mut handler) = memory_handler(config, 64 * 1024);
+    let started = Instant::now();
+    let error = loop {
+        if let Err(e) = handler.handle(0) {
+            break e;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "The handler never timed out");
+    };
+    assert!(
+        matches!(error, ServerError::Timeout { kind: TimeoutKind::Read, .. }),
+        "{:?}",
+        error
+    );
+    assert!(handler.is_closed());
+    drop(peer);
+
+    // A peer that never reads fills the pipe and runs into the write timeout
+    let config = ServerConfig {
+        write_timeout: Some(Duration::from_millis(100)),
+        ..ServerConfig::default()
+    };
+    let (mut peer, mut handler) = memory_handler(config, 64);
+    let request = framing::encode_frame(&echo(&"x".repeat(200)));
+    let writing = thread::spawn(move || {
+        peer.write_all(&request).unwrap();
+        peer
+    });
+    let started = Instant::now();
+    let error = loop {
+        if let Err(e) = handler.handle(0) {
+            break e;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "The handler never timed out");
+    };
+    assert!(
+        matches!(error, ServerError::Timeout { kind: TimeoutKind::Write, .. }),
+        "{:?}",
+        error
+    );
+
+    // Once shut down by the handler, the peer sees the end of the stream
+    let mut peer = writing.join().unwrap();
+    let mut rest = Vec::new();
+    peer.read_to_end(&mut rest).unwrap();
+    assert!(rest.len() <= 64);
+    assert_eq!(peer.write(b"more").unwrap_err().kind(), ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn test_server_over_memory_listener() {
+    let listener = MemoryListener::new(64 * 1024);
+    let config = ServerConfig {
+        workers: 2,
+        ..ServerConfig::default()
+    };
+    let server = Arc::new(Server::with_acceptor(listener.clone(), config));
+    let handle = {
+        let server = Arc::clone(&server);
+        thread::spawn(move || server.run(0))
+    };
+
+    // More clients than workers, all of them are served
+    let mut peers: Vec<MemoryStream> = (0..3).map(|_| listener.connect()).collect();
+    for (number, peer) in peers.iter_mut().enumerate() {
+        peer.set_timeout(Some(Duration::from_secs(5)));
+        peer.write_all(&framing::encode_frame(&echo(&format!("client {}", number)))).unwrap();
+    }
+    for (number, peer) in peers.iter_mut().enumerate() {
+        let frame = framing::read_frame(peer, framing::DEFAULT_MAX_FRAME_SIZE).unwrap();
+        assert_eq!(echoed(&ServerMessage::decode(frame.as_slice()).unwrap()), format!("client {}", number));
+    }
+    assert_eq!(server.stats().active_connections, 3);
+
+    // A request in flight when the server stops is still answered, then every connection is closed
+    let request = framing::encode_frame(&echo("draining"));
+    peers[0].write_all(&request[..3]).unwrap();
+    server.stop();
+    thread::sleep(Duration::from_millis(100));
+    peers[0].write_all(&request[3..]).unwrap();
+    assert!(handle.join().unwrap().is_ok(), "The server did not drain");
+    let frame = framing::read_frame(&mut peers[0], framing::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    assert_eq!(echoed(&ServerMessage::decode(frame.as_slice()).unwrap()), "draining");
+    for peer in peers.iter_mut() {
+        assert_eq!(peer.read(&mut [0u8; 1]).unwrap(), 0);
+    }
+    assert_eq!(server.stats().active_connections, 0);
+}
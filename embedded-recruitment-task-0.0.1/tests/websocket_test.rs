@@ -0,0 +1,99 @@
+use embedded_recruitment_task::{
+    message::{client_message, server_message, AddRequest, ClientMessage, EchoMessage, ServerMessage},
+    server::ServerConfig,
+};
+use prost::Message as _;
+use tungstenite::Message;
+
+mod common;
+
+/* Sends one ClientMessage as a binary WebSocket message and decodes the binary answer */
+fn call<S: std::io::Read + std::io::Write>(
+    websocket: &mut tungstenite::WebSocket<S>,
+    message: client_message::Message,
+) -> ServerMessage {
+    let request = ClientMessage {
+        message: Some(message),
+        ..Default::default()
+    };
+    websocket
+        .send(Message::binary(request.encode_to_vec()))
+        .expect("Failed to send WebSocket message");
+
+    match websocket.read().expect("Failed to read WebSocket message") {
+        Message::Binary(payload) => {
+            ServerMessage::decode(payload.as_ref()).expect("Failed to decode ServerMessage")
+        }
+        other => panic!("Expected a binary message, but received {:?}", other),
+    }
+}
+
+#[test]
+fn test_websocket_echo_and_add() {
+    // Start a server with a WebSocket listener next to the TCP one
+    let (server, _, handle) = common::start_server(0, ServerConfig::default(), |server| {
+        server.enable_websocket("127.0.0.1:0").expect("Failed to bind WebSocket listener");
+    });
+    let websocket_addr = server.websocket_addr().unwrap();
+
+    // Connect a WebSocket client
+    let (mut websocket, _) = tungstenite::connect(format!("ws://{}", websocket_addr))
+        .expect("Failed to connect to the WebSocket listener");
+
+    // Echo
+    let echo_message = EchoMessage {
+        content: "Hello, WebSocket!".to_string(),
+    };
+    let response = call(
+        &mut websocket,
+        client_message::Message::EchoMessage(echo_message.clone()),
+    );
+    match response.message {
+        Some(server_message::Message::EchoMessage(echo)) => {
+            assert_eq!(echo.content, echo_message.content, "Echoed message content does not match");
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    // Add
+    let response = call(
+        &mut websocket,
+        client_message::Message::AddRequest(AddRequest { a: 4, b: 38 }),
+    );
+    match response.message {
+        Some(server_message::Message::AddResponse(add_response)) => {
+            assert_eq!(add_response.result, 42, "AddResponse result does not match");
+        }
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    // Disconnect and stop the server
+    websocket.close(None).expect("Failed to close the WebSocket");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_websocket_rejects_invalid_payload() {
+    let (server, _, handle) = common::start_server(1, ServerConfig::default(), |server| {
+        server.enable_websocket("127.0.0.1:0").expect("Failed to bind WebSocket listener");
+    });
+    let websocket_addr = server.websocket_addr().unwrap();
+
+    let (mut websocket, _) = tungstenite::connect(format!("ws://{}", websocket_addr))
+        .expect("Failed to connect to the WebSocket listener");
+
+    // A payload that is not a ClientMessage makes the server close the connection
+    websocket
+        .send(Message::binary(vec![0xff, 0xff, 0xff]))
+        .expect("Failed to send WebSocket message");
+    match websocket.read() {
+        Ok(Message::Close(Some(frame))) => {
+            assert_eq!(u16::from(frame.code), 1007, "Unexpected close code");
+        }
+        other => panic!("Expected a close frame, but received {:?}", other),
+    }
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
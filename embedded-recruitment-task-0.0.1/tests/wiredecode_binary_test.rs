@@ -0,0 +1,91 @@
+use embedded_recruitment_task::{
+    framing, hex,
+    message::{client_message, server_message, AddRequest, AddResponse, ClientMessage, EchoMessage, ServerMessage},
+};
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn wiredecode(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wiredecode"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start the wiredecode binary");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().expect("Failed to wait for the wiredecode binary")
+}
+
+fn client_capture() -> Vec<u8> {
+    let mut capture = framing::encode_frame(&ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "hi".to_string(),
+        })),
+        ..Default::default()
+    });
+    capture.extend(framing::encode_frame(&ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a: 1, b: 2 })),
+        ..Default::default()
+    }));
+    capture
+}
+
+#[test]
+fn test_binary_and_hex_captures() {
+    let capture = client_capture();
+    let expected = "\
+0x0000  frame 1, 6 bytes
+        {\"message\":{\"echo_message\":{\"content\":\"hi\"}}}
+0x0007  frame 2, 6 bytes
+        {\"message\":{\"add_request\":{\"a\":1,\"b\":2}}}
+2 frames, 14 bytes
+";
+
+    let output = wiredecode(&[], &capture);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    // The same bytes as plain hex and as xxd output
+    let output = wiredecode(&[], hex::encode_spaced(&capture).as_bytes());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    let xxd = "00000000: 060a 040a 0268 6906 1204 0801 1002       .....hi.......\n";
+    let output = wiredecode(&["--format", "hex"], xxd.as_bytes());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    // hexdump -C output, its last line is the offset after the last byte
+    let hexdump = "00000000  06 0a 04 0a 02 68 69 06  12 04 08 01 10 02        |.....hi.......|\n0000000e\n";
+    let output = wiredecode(&[], hexdump.as_bytes());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+#[test]
+fn test_grouped_hex_keeps_its_first_group() {
+    // Groups of 4 bytes look like an offset column but there is no ASCII column: every group is data
+    let output = wiredecode(&[], b"060a040a 02686906 12040801 1002\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with("2 frames, 14 bytes\n"), "{}", stdout);
+    assert!(stdout.contains("{\"message\":{\"echo_message\":{\"content\":\"hi\"}}}"), "{}", stdout);
+}
+
+#[test]
+fn test_server_direction_and_errors() {
+    let mut capture = framing::encode_frame(&ServerMessage {
+        message: Some(server_message::Message::AddResponse(AddResponse { result: 3 })),
+    });
+    // A frame that is not a message, then a frame cut short
+    capture.extend([0x02, 0xff, 0xff]);
+    capture.extend([0x05, 0x0a]);
+
+    let output = wiredecode(&["--direction", "server", "--format", "binary"], &capture);
+    assert_eq!(output.status.code(), Some(2), "Undecodable bytes must be reported in the exit code");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("{\"add_response\":{\"result\":3}}"), "{}", stdout);
+    assert!(stdout.contains("0x0005  frame 2, 2 bytes\n        decode error:"), "{}", stdout);
+    assert!(stdout.contains("        payload: ff ff"), "{}", stdout);
+    assert!(stdout.contains("0x0008  truncated frame: 5 bytes announced, only 1 captured"), "{}", stdout);
+}
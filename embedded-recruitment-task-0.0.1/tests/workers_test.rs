@@ -0,0 +1,85 @@
+use embedded_recruitment_task::{
+    framing,
+    message::{client_message, server_message, ClientMessage, EchoMessage, ServerMessage},
+    server::ServerConfig,
+};
+use prost::Message;
+use std::{
+    io::Write,
+    net::TcpStream,
+    time::Duration,
+};
+
+mod common;
+
+fn echo(stream: &mut TcpStream, content: &str) -> std::io::Result<String> {
+    let request = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        ..Default::default()
+    };
+    stream.write_all(&framing::encode_frame(&request))?;
+    let frame = framing::read_frame(stream, framing::DEFAULT_MAX_FRAME_SIZE)?;
+    match ServerMessage::decode(frame.as_slice())?.message {
+        Some(server_message::Message::EchoMessage(echo)) => Ok(echo.content),
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+}
+
+#[test]
+fn test_more_clients_than_workers_are_all_served() {
+    let config = ServerConfig {
+        workers: 2,
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(0, config, |_| {});
+
+    // Five clients stay connected at the same time on two workers, every one of them is answered
+    let mut clients: Vec<TcpStream> = (0..5)
+        .map(|_| {
+            let stream = TcpStream::connect(addr).expect("Failed to connect to the server");
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            stream
+        })
+        .collect();
+    for round in 0..3 {
+        for (index, client) in clients.iter_mut().enumerate().rev() {
+            let content = format!("client {} round {}", index, round);
+            assert_eq!(echo(client, &content).expect("A client was not served"), content);
+        }
+    }
+
+    // A client that arrives later is served too while the others are still connected
+    let mut late = TcpStream::connect(addr).expect("Failed to connect to the server");
+    late.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(echo(&mut late, "late").expect("The late client was not served"), "late");
+
+    drop(late);
+    drop(clients);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_connections_above_the_limit_are_rejected() {
+    let config = ServerConfig {
+        max_connections: Some(1),
+        ..ServerConfig::default()
+    };
+    let (server, addr, handle) = common::start_server(1, config, |_| {});
+
+    let mut first = TcpStream::connect(addr).expect("Failed to connect to the server");
+    assert_eq!(echo(&mut first, "first").unwrap(), "first");
+
+    // The second client is over the limit and gets disconnected
+    let mut second = TcpStream::connect(addr).expect("Failed to connect to the server");
+    second.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert!(echo(&mut second, "second").is_err(), "A client above the limit was served");
+    assert_eq!(server.stats().rejected_connections, 1, "The rejection was not counted");
+
+    drop(first);
+    drop(second);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}